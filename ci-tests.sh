@@ -2,11 +2,12 @@
 set -e
 TARGET="$([ -n "$1" ] && echo "--target $1" || echo "")"
 
-COMMON_FEATURES="xchachapoly vector-tests"
+COMMON_FEATURES="xchachapoly curve448 aesgcmsiv sha3 blake3 risky-fixed-ephemeral vector-tests"
 
 set -x
 cargo check --benches
 cargo test $TARGET --no-default-features
+cargo test $TARGET --no-default-features --features default-resolver
 cargo test $TARGET --features "$COMMON_FEATURES"
 cargo test $TARGET --features "ring-resolver $COMMON_FEATURES"
 cargo test $TARGET --features "ring-accelerated $COMMON_FEATURES"
@@ -1,31 +1,146 @@
-use std::fmt::Debug;
+use alloc::{borrow::Cow, boxed::Box, sync::Arc, vec, vec::Vec};
+use core::fmt::Debug;
 
 #[cfg(feature = "hfs")]
 use crate::params::HandshakeModifier;
 use crate::{
     cipherstate::{CipherState, CipherStates},
-    constants::{MAXDHLEN, PSKLEN},
+    constants::{MAXDHLEN, MAXHASHLEN, MAXMSGLEN, PSKLEN, TAGLEN},
     error::{Error, InitStage, Prerequisite},
     handshakestate::HandshakeState,
+    observer::{Observer, SessionObserver},
     params::NoiseParams,
     resolvers::{BoxedCryptoResolver, CryptoResolver},
+    symmetricstate::SymmetricState,
+    transportstate::RekeyPolicy,
+    types::{Dh, Random},
     utils::Toggle,
 };
 use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
 /// The maximum number of PSKs we will allocate for.
 const MAX_PSKS: usize = 10;
 
-/// A keypair object returned by [`Builder::generate_keypair()`]
+/// The label `Builder::bind_to_session()` puts in front of the parent session's handshake hash.
+const REHANDSHAKE_LABEL: &[u8] = b"snow rehandshake";
+
+/// A keypair object returned by [`Builder::generate_keypair()`], and accepted by
+/// [`Builder::local_keypair()`].
+///
+/// The private key is zeroed when the keypair is dropped, and left out of the `Debug` output.
 ///
 /// [`generate_keypair()`]: #method.generate_keypair
 pub struct Keypair {
     /// The private asymmetric key
-    pub private: Vec<u8>,
+    pub private: Zeroizing<Vec<u8>>,
     /// The public asymmetric key
     pub public:  Vec<u8>,
 }
 
+impl Keypair {
+    /// The private asymmetric key.
+    #[must_use]
+    pub fn private(&self) -> &[u8] {
+        &self.private
+    }
+
+    /// The public asymmetric key.
+    #[must_use]
+    pub fn public(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// The public key as base64, e.g. to hand to the peer. See [`crate::keys`].
+    #[cfg(feature = "keys")]
+    #[must_use]
+    pub fn public_base64(&self) -> alloc::string::String {
+        crate::keys::to_base64(&self.public)
+    }
+
+    /// The public key as lowercase hex. See [`crate::keys`].
+    #[cfg(feature = "keys")]
+    #[must_use]
+    pub fn public_hex(&self) -> alloc::string::String {
+        crate::keys::to_hex(&self.public)
+    }
+
+    /// A copy of the public key, e.g. to keep in a configuration file.
+    #[must_use]
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.public.clone())
+    }
+}
+
+impl Debug for Keypair {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Keypair").field("public", &self.public).finish_non_exhaustive()
+    }
+}
+
+/// The public half of a [`Keypair`], or a peer's public key.
+///
+/// It's displayed and parsed as lowercase hex, and with the `serde` feature it's (de)serialized
+/// the same way, so a configuration file can hold public keys but never a private one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PublicKey(Vec<u8>);
+
+impl PublicKey {
+    /// The raw public key.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for PublicKey {
+    fn from(public: Vec<u8>) -> Self {
+        PublicKey(public)
+    }
+}
+
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl core::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl core::str::FromStr for PublicKey {
+    type Err = Error;
+
+    /// Parse hex of either case. The key's length is checked when it's given to the `Builder`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() || s.len() & 1 != 0 {
+            return Err(Error::Input);
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| Error::Input))
+            .collect::<Result<_, _>>()
+            .map(PublicKey)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PublicKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        alloc::string::String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl PartialEq for Keypair {
     fn eq(&self, other: &Keypair) -> bool {
         let priv_eq = self.private.ct_eq(&other.private);
@@ -41,11 +156,11 @@ impl PartialEq for Keypair {
 /// # Examples
 ///
 /// ```
-/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # fn main() -> Result<(), snow::Error> {
 /// # use snow::Builder;
 /// # let my_long_term_key = [0u8; 32];
 /// # let their_pub_key = [0u8; 32];
-/// # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+/// # #[cfg(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated")))]
 /// let noise = Builder::new("Noise_XX_25519_ChaChaPoly_BLAKE2s".parse()?)
 ///     .local_private_key(&my_long_term_key)?
 ///     .remote_public_key(&their_pub_key)?
@@ -58,14 +173,24 @@ pub struct Builder<'builder> {
     params:   NoiseParams,
     resolver: BoxedCryptoResolver,
     s:        Option<&'builder [u8]>,
+    s_dh:     Option<Box<dyn Dh>>,
     e_fixed:  Option<&'builder [u8]>,
-    rs:       Option<&'builder [u8]>,
+    rs:       Option<Cow<'builder, [u8]>>,
     psks:     [Option<&'builder [u8; 32]>; MAX_PSKS],
-    plog:     Option<&'builder [u8]>,
+    plog:     Option<Cow<'builder, [u8]>>,
+    bound:    Option<Zeroizing<Vec<u8>>>,
+    rekey:    Option<RekeyPolicy>,
+    max_len:  Option<usize>,
+    psk_chk:  bool,
+    dh_chk:   bool,
+    rng:      Option<Box<dyn Random>>,
+    observer: Option<Arc<dyn SessionObserver>>,
+    #[cfg(feature = "vectors")]
+    trace:    bool,
 }
 
 impl<'builder> Debug for Builder<'builder> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Builder").field("params", &self.params.name).finish_non_exhaustive()
     }
 }
@@ -108,10 +233,31 @@ impl<'builder> Builder<'builder> {
     /// Create a Builder with a custom crypto resolver.
     #[must_use]
     pub fn with_resolver(params: NoiseParams, resolver: BoxedCryptoResolver) -> Self {
-        Builder { params, resolver, s: None, e_fixed: None, rs: None, plog: None, psks: [None; 10] }
+        Builder {
+            params,
+            resolver,
+            s: None,
+            s_dh: None,
+            e_fixed: None,
+            rs: None,
+            plog: None,
+            psks: [None; 10],
+            bound: None,
+            rekey: None,
+            max_len: None,
+            psk_chk: false,
+            dh_chk: false,
+            rng: None,
+            observer: None,
+            #[cfg(feature = "vectors")]
+            trace: false,
+        }
     }
 
-    /// Specify a PSK (only used with `NoisePSK` base parameter)
+    /// Specify a PSK for the `pskN` modifier at `location` (e.g. `0` for `psk0`).
+    ///
+    /// Each position named in the protocol string needs its own PSK, e.g.
+    /// `Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s` needs `.psk(3, ..)`.
     ///
     /// # Errors
     /// * `InitError(InitStage::ValidatePskPosition)` if the location is a number larger than
@@ -129,6 +275,58 @@ impl<'builder> Builder<'builder> {
         }
     }
 
+    /// Use a secret from `TransportState::resumption_secret()` of an earlier session as the
+    /// PSK, at the position of the pattern's first `psk` modifier (e.g. 0 for `NNpsk0`).
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ValidatePskPosition)` if the pattern has no `psk` modifier.
+    /// * `InitError(InitStage::ParameterOverwrite)` if a PSK was already set at that position.
+    pub fn resumption_psk(self, secret: &'builder [u8; PSKLEN]) -> Result<Self, Error> {
+        let location =
+            self.params.handshake.modifiers.list.iter().find_map(|modifier| match *modifier {
+                crate::params::HandshakeModifier::Psk(n) => Some(n),
+                _ => None,
+            });
+        match location {
+            Some(location) => self.psk(location, secret),
+            None => Err(InitStage::ValidatePskPosition.into()),
+        }
+    }
+
+    /// Reject PSKs that are obviously not uniformly random, i.e. a single repeated byte such
+    /// as all zeros, when building and in `HandshakeState::set_psk()`.
+    ///
+    /// The Noise spec requires PSKs to be 32 uniformly random bytes, but some protocols use a
+    /// fixed all-zero PSK to mean "no PSK" (e.g. WireGuard), so this check is opt-in.
+    #[must_use]
+    pub fn reject_weak_psks(mut self) -> Self {
+        self.psk_chk = true;
+        self
+    }
+
+    /// Reject DHs with a low-order public key, i.e. fail with `Error::Dh` if a DH results in
+    /// all zeros, so a peer can't force a predictable shared secret (see the "non-contributory"
+    /// discussion in the Noise spec).
+    ///
+    /// This applies to the remote static key from `remote_public_key()` as well as to keys
+    /// received during the handshake. `448` always performs this check.
+    #[must_use]
+    pub fn reject_low_order_keys(mut self) -> Self {
+        self.dh_chk = true;
+        self
+    }
+
+    /// Your static keypair, as returned by [`generate_keypair()`]. Equivalent to
+    /// `local_private_key(keypair.private())`.
+    ///
+    /// [`generate_keypair()`]: #method.generate_keypair
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn local_keypair(self, keypair: &'builder Keypair) -> Result<Self, Error> {
+        self.local_private_key(keypair.private())
+    }
+
     /// Your static private key (can be generated with [`generate_keypair()`]).
     ///
     /// [`generate_keypair()`]: #method.generate_keypair
@@ -136,7 +334,7 @@ impl<'builder> Builder<'builder> {
     /// # Errors
     /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
     pub fn local_private_key(mut self, key: &'builder [u8]) -> Result<Self, Error> {
-        if self.s.is_some() {
+        if self.s.is_some() || self.s_dh.is_some() {
             Err(InitStage::ParameterOverwrite.into())
         } else {
             self.s = Some(key);
@@ -144,16 +342,49 @@ impl<'builder> Builder<'builder> {
         }
     }
 
-    #[doc(hidden)]
+    /// Use `dh` as the local static keypair, in place of
+    /// [`local_private_key()`](Self::local_private_key).
+    ///
+    /// The handshake only calls `pubkey()` and `dh()` on it, so the private key never has to be
+    /// handed to snow: a `Dh` whose `dh()` is performed by an HSM or TPM works as long as its
+    /// name and key length match the DH algorithm in the params.
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method or `local_private_key()` has
+    ///   been called previously.
+    pub fn local_static_dh(mut self, dh: Box<dyn Dh>) -> Result<Self, Error> {
+        if self.s.is_some() || self.s_dh.is_some() {
+            Err(InitStage::ParameterOverwrite.into())
+        } else {
+            self.s_dh = Some(dh);
+            Ok(self)
+        }
+    }
+
+    /// Use `key` as the local ephemeral private key instead of generating a fresh one.
+    ///
+    /// This exists to reproduce the published test vectors and to make interop debugging
+    /// deterministic. **Never use it in production**: reusing an ephemeral key destroys the
+    /// forward secrecy and identity hiding properties of the handshake. The
+    /// "risky-fixed-ephemeral" feature has to be enabled to use this function.
+    #[cfg(feature = "risky-fixed-ephemeral")]
     #[must_use]
     pub fn fixed_ephemeral_key_for_testing_only(mut self, key: &'builder [u8]) -> Self {
         self.e_fixed = Some(key);
         self
     }
 
+    /// Keep a trace of the handshake's symmetric state, including its secrets, for
+    /// `Vector::record()`. Other handshakes never keep one.
+    #[cfg(feature = "vectors")]
+    pub(crate) fn trace_symmetric_state(mut self) -> Self {
+        self.trace = true;
+        self
+    }
+
     /// Arbitrary data to be hashed in to the handshake hash value.
     ///
-    /// This may only be set once
+    /// This may only be set once, and not after `append_prologue()`.
     ///
     /// # Errors
     /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
@@ -161,11 +392,53 @@ impl<'builder> Builder<'builder> {
         if self.plog.is_some() {
             Err(InitStage::ParameterOverwrite.into())
         } else {
-            self.plog = Some(key);
+            self.plog = Some(Cow::Borrowed(key));
             Ok(self)
         }
     }
 
+    /// Append `data` to the prologue, e.g. to add negotiation data that is only known once
+    /// the rest of the builder has been set up. The parts are concatenated in the order they
+    /// were added, after any set with `prologue()`.
+    #[must_use]
+    pub fn append_prologue(mut self, data: &[u8]) -> Self {
+        self.plog.get_or_insert(Cow::Borrowed(&[])).to_mut().extend_from_slice(data);
+        self
+    }
+
+    /// Bind the handshake to the existing session with the handshake hash `handshake_hash`,
+    /// for a re-handshake run inside it, e.g. to rotate static keys. The hash is appended to
+    /// the prologue under a fixed label, so the new handshake only succeeds if both sides run
+    /// it from the same session. Switch over with [`TransportState::complete_rehandshake()`]
+    /// once it's done, which checks that it's that session.
+    ///
+    /// [`TransportState::complete_rehandshake()`]: crate::TransportState::complete_rehandshake
+    ///
+    /// # Errors
+    /// * `Error::Input` if `handshake_hash` is longer than the hash of any supported hash
+    ///   function.
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn bind_to_session(mut self, handshake_hash: &[u8]) -> Result<Self, Error> {
+        if self.bound.is_some() {
+            return Err(InitStage::ParameterOverwrite.into());
+        }
+        let len = u8::try_from(handshake_hash.len())
+            .ok()
+            .filter(|&len| usize::from(len) <= MAXHASHLEN)
+            .ok_or(Error::Input)?;
+        self.bound = Some(Zeroizing::new(handshake_hash.to_vec()));
+        Ok(self
+            .append_prologue(REHANDSHAKE_LABEL)
+            .append_prologue(&[len])
+            .append_prologue(handshake_hash))
+    }
+
+    /// The prologue the handshake will be built with, e.g. for logging.
+    #[must_use]
+    pub fn get_prologue(&self) -> &[u8] {
+        self.plog.as_deref().unwrap_or(&[])
+    }
+
     /// The responder's static public key.
     ///
     /// # Errors
@@ -174,11 +447,112 @@ impl<'builder> Builder<'builder> {
         if self.rs.is_some() {
             Err(InitStage::ParameterOverwrite.into())
         } else {
-            self.rs = Some(pub_key);
+            self.rs = Some(Cow::Borrowed(pub_key));
+            Ok(self)
+        }
+    }
+
+    /// Like [`remote_public_key()`](Self::remote_public_key), but with the key encoded as
+    /// (standard, padded) base64, e.g. from a WireGuard-style configuration file.
+    ///
+    /// # Errors
+    /// * `Error::Input` if `pub_key` isn't valid base64 of a key of the right length.
+    /// * `InitError(InitStage::ParameterOverwrite)` if a remote public key was set previously.
+    #[cfg(feature = "keys")]
+    pub fn remote_public_key_b64(self, pub_key: &str) -> Result<Self, Error> {
+        let pub_key = crate::keys::public_key_from_base64(self.params.dh, pub_key)?;
+        self.remote_public_key_owned(pub_key)
+    }
+
+    /// Like [`remote_public_key()`](Self::remote_public_key), but with the key encoded as hex.
+    ///
+    /// # Errors
+    /// * `Error::Input` if `pub_key` isn't valid hex of a key of the right length.
+    /// * `InitError(InitStage::ParameterOverwrite)` if a remote public key was set previously.
+    #[cfg(feature = "keys")]
+    pub fn remote_public_key_hex(self, pub_key: &str) -> Result<Self, Error> {
+        let pub_key = crate::keys::public_key_from_hex(self.params.dh, pub_key)?;
+        self.remote_public_key_owned(pub_key)
+    }
+
+    #[cfg(feature = "keys")]
+    fn remote_public_key_owned(mut self, pub_key: Vec<u8>) -> Result<Self, Error> {
+        if self.rs.is_some() {
+            Err(InitStage::ParameterOverwrite.into())
+        } else {
+            self.rs = Some(Cow::Owned(pub_key));
+            Ok(self)
+        }
+    }
+
+    /// Automatically rekey the transport ciphers according to `policy` once the handshake has
+    /// finished. Both peers must use the same policy.
+    ///
+    /// Only honored by [`TransportState`](crate::TransportState); the stateless transport leaves
+    /// rekeying to the application, since it has no ordering of its own to count against.
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ValidateRekeyPolicy)` if the policy's threshold is zero.
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn rekey_policy(mut self, policy: RekeyPolicy) -> Result<Self, Error> {
+        if self.rekey.is_some() {
+            Err(InitStage::ParameterOverwrite.into())
+        } else if !policy.is_valid() {
+            Err(InitStage::ValidateRekeyPolicy.into())
+        } else {
+            self.rekey = Some(policy);
+            Ok(self)
+        }
+    }
+
+    /// Cap every handshake and transport message, in both directions, at `len` bytes instead of
+    /// the Noise maximum of 65535 (e.g. to stay within a UDP MTU). Both peers should use the
+    /// same limit: writes that would exceed it and reads of longer messages fail with
+    /// `Error::Input`.
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ValidateMaxMessageLen)` if `len` is not larger than the AEAD tag
+    ///   or is above 65535.
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn max_message_len(mut self, len: usize) -> Result<Self, Error> {
+        if self.max_len.is_some() {
+            Err(InitStage::ParameterOverwrite.into())
+        } else if len <= TAGLEN || len > MAXMSGLEN {
+            Err(InitStage::ValidateMaxMessageLen.into())
+        } else {
+            self.max_len = Some(len);
             Ok(self)
         }
     }
 
+    /// Use `rng` for this session's ephemeral keys instead of the one from the resolver, e.g.
+    /// a seeded RNG in tests or a certified DRBG.
+    ///
+    /// This only applies to the built `HandshakeState` (and any fallback made from it), not to
+    /// [`generate_keypair()`](Self::generate_keypair).
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn rng(mut self, rng: Box<dyn Random>) -> Result<Self, Error> {
+        if self.rng.is_some() {
+            return Err(InitStage::ParameterOverwrite.into());
+        }
+        self.rng = Some(rng);
+        Ok(self)
+    }
+
+    /// Report the events of this session to `observer`, see [`SessionObserver`].
+    ///
+    /// # Errors
+    /// * `InitError(InitStage::ParameterOverwrite)` if this method has been called previously.
+    pub fn observer(mut self, observer: Arc<dyn SessionObserver>) -> Result<Self, Error> {
+        if self.observer.is_some() {
+            return Err(InitStage::ParameterOverwrite.into());
+        }
+        self.observer = Some(observer);
+        Ok(self)
+    }
+
     // TODO: performance issue w/ creating a new RNG and DH instance per call.
     /// Generate a new asymmetric keypair (for use as a static key).
     ///
@@ -188,7 +562,7 @@ impl<'builder> Builder<'builder> {
     pub fn generate_keypair(&self) -> Result<Keypair, Error> {
         let mut rng = self.resolver.resolve_rng().ok_or(InitStage::GetRngImpl)?;
         let mut dh = self.resolver.resolve_dh(&self.params.dh).ok_or(InitStage::GetDhImpl)?;
-        let mut private = vec![0u8; dh.priv_len()];
+        let mut private = Zeroizing::new(vec![0u8; dh.priv_len()]);
         let mut public = vec![0u8; dh.pub_len()];
         dh.generate(&mut *rng);
 
@@ -217,15 +591,24 @@ impl<'builder> Builder<'builder> {
     }
 
     fn build(self, initiator: bool) -> Result<HandshakeState, Error> {
-        if self.s.is_none() && self.params.handshake.pattern.needs_local_static_key(initiator) {
+        // The key requirements are those of the base pattern, where Alice is the initiator.
+        // A fallback pattern is Bob-initiated, see `HandshakeState::into_fallback()`.
+        let alice = initiator != self.params.handshake.is_fallback();
+        if self.s.is_none()
+            && self.s_dh.is_none()
+            && self.params.handshake.pattern.needs_local_static_key(alice)
+        {
             return Err(Prerequisite::LocalPrivateKey.into());
         }
 
-        if self.rs.is_none() && self.params.handshake.pattern.need_known_remote_pubkey(initiator) {
+        if self.rs.is_none() && self.params.handshake.pattern.need_known_remote_pubkey(alice) {
             return Err(Prerequisite::RemotePublicKey.into());
         }
 
-        let rng = self.resolver.resolve_rng().ok_or(InitStage::GetRngImpl)?;
+        let rng = match self.rng {
+            Some(rng) => rng,
+            None => self.resolver.resolve_rng().ok_or(InitStage::GetRngImpl)?,
+        };
         let cipher =
             self.resolver.resolve_cipher(&self.params.cipher).ok_or(InitStage::GetCipherImpl)?;
         let hash = self.resolver.resolve_hash(&self.params.hash).ok_or(InitStage::GetHashImpl)?;
@@ -238,22 +621,38 @@ impl<'builder> Builder<'builder> {
         let handshake_cipherstate = CipherState::new(cipher);
         let cipherstates = CipherStates::new(CipherState::new(cipher1), CipherState::new(cipher2))?;
 
-        let s = match self.s {
-            Some(k) => {
+        let external_s = self.s_dh.is_some();
+        let s = match (self.s, self.s_dh) {
+            (_, Some(dh)) => {
+                if dh.name() != s_dh.name() || dh.pub_len() != s_dh.pub_len() {
+                    return Err(InitStage::ValidateDhTypes.into());
+                }
+                Toggle::on(dh)
+            },
+            (Some(k), None) => {
+                if k.len() != s_dh.priv_len() {
+                    return Err(InitStage::ValidatePrivateKeyLength.into());
+                }
                 (*s_dh).set(k);
                 Toggle::on(s_dh)
             },
-            None => Toggle::off(s_dh),
+            (None, None) => Toggle::off(s_dh),
         };
 
         if let Some(fixed_k) = self.e_fixed {
+            if fixed_k.len() != e_dh.priv_len() {
+                return Err(InitStage::ValidatePrivateKeyLength.into());
+            }
             (*e_dh).set(fixed_k);
         }
         let e = Toggle::off(e_dh);
 
         let mut rs_buf = [0u8; MAXDHLEN];
-        let rs = match self.rs {
+        let rs = match self.rs.as_deref() {
             Some(v) => {
+                if v.len() != e.pub_len() {
+                    return Err(InitStage::ValidateRemotePublicKeyLength.into());
+                }
                 rs_buf[..v.len()].copy_from_slice(v);
                 Toggle::on(rs_buf)
             },
@@ -262,22 +661,27 @@ impl<'builder> Builder<'builder> {
 
         let re = Toggle::off([0u8; MAXDHLEN]);
 
-        let mut psks = [None::<[u8; PSKLEN]>; 10];
+        let mut psks = Zeroizing::new([None::<[u8; PSKLEN]>; 10]);
         for (i, psk) in self.psks.iter().enumerate() {
             if let Some(key) = *psk {
                 if key.len() != PSKLEN {
                     return Err(InitStage::ValidatePskLengths.into());
+                } else if self.psk_chk && crate::utils::is_weak_psk(key) {
+                    return Err(InitStage::ValidatePskStrength.into());
                 }
                 let mut k = [0u8; PSKLEN];
                 k.copy_from_slice(key);
                 psks[i] = Some(k);
+                k.zeroize();
             }
         }
 
+        let symmetricstate = SymmetricState::new(handshake_cipherstate, hash);
+        #[cfg(feature = "vectors")]
+        let symmetricstate = if self.trace { symmetricstate.traced() } else { symmetricstate };
         let mut hs = HandshakeState::new(
             rng,
-            handshake_cipherstate,
-            hash,
+            symmetricstate,
             s,
             e,
             self.e_fixed.is_some(),
@@ -286,9 +690,16 @@ impl<'builder> Builder<'builder> {
             initiator,
             self.params,
             &psks,
-            self.plog.unwrap_or(&[]),
+            self.plog.as_deref().unwrap_or(&[]),
             cipherstates,
         )?;
+        hs.external_s = external_s;
+        hs.rekey_policy = self.rekey;
+        hs.bound_hash = self.bound;
+        hs.max_message_len = self.max_len.unwrap_or(MAXMSGLEN);
+        hs.reject_weak_psks = self.psk_chk;
+        hs.reject_zero_dh = self.dh_chk;
+        hs.observer = Observer::new(self.observer);
         Self::resolve_kem(self.resolver, &mut hs)?;
         Ok(hs)
     }
@@ -321,9 +732,11 @@ impl<'builder> Builder<'builder> {
 #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
 mod tests {
     use super::*;
+    use crate::{params::DHChoice, resolvers::DefaultResolver};
     type TestResult = Result<(), Box<dyn std::error::Error>>;
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_builder() -> TestResult {
         let _noise = Builder::new("Noise_NN_25519_ChaChaPoly_SHA256".parse()?)
             .prologue(&[2, 2, 2, 2, 2, 2, 2, 2])?
@@ -333,6 +746,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_builder_keygen() -> TestResult {
         let builder = Builder::new("Noise_NN_25519_ChaChaPoly_SHA256".parse()?);
         let key1 = builder.generate_keypair();
@@ -341,6 +755,90 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_builder_local_keypair() -> TestResult {
+        let builder = Builder::new("Noise_XN_25519_ChaChaPoly_SHA256".parse()?);
+        let keypair = builder.generate_keypair()?;
+        assert!(!format!("{keypair:?}").contains(&format!("{:?}", keypair.private())));
+        let builder = builder.local_keypair(&keypair)?;
+        assert_eq!(
+            builder.local_private_key(&[0u8; 32]).unwrap_err(),
+            Error::Init(InitStage::ParameterOverwrite)
+        );
+        let builder = Builder::new("Noise_XN_25519_ChaChaPoly_SHA256".parse()?);
+        builder.local_keypair(&keypair)?.build_initiator()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_public_key() -> TestResult {
+        let builder = Builder::new("Noise_NK_25519_ChaChaPoly_SHA256".parse()?);
+        let public = builder.generate_keypair()?.public_key();
+        let parsed: PublicKey = public.to_string().parse()?;
+        assert_eq!(parsed, public);
+        assert_eq!(public.to_string().to_uppercase().parse::<PublicKey>()?, public);
+        assert_eq!("abc".parse::<PublicKey>().unwrap_err(), Error::Input);
+        assert_eq!("zz".parse::<PublicKey>().unwrap_err(), Error::Input);
+        builder.remote_public_key(parsed.as_bytes())?.build_initiator()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_public_key_serde() -> TestResult {
+        let public = PublicKey::from(vec![0x00, 0xab, 0xff]);
+        assert_eq!(serde_json::to_string(&public)?, r#""00abff""#);
+        assert_eq!(serde_json::from_str::<PublicKey>(r#""00ABff""#)?, public);
+        assert!(serde_json::from_str::<PublicKey>(r#""00ab0""#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_builder_key_lengths() -> TestResult {
+        let params: NoiseParams = "Noise_KK_25519_ChaChaPoly_SHA256".parse()?;
+        let build = |private: &[u8], public: &[u8]| {
+            Builder::new(params.clone())
+                .local_private_key(private)?
+                .remote_public_key(public)?
+                .build_initiator()
+        };
+        assert_eq!(
+            build(&[1u8; 31], &[1u8; 32]).unwrap_err(),
+            Error::Init(InitStage::ValidatePrivateKeyLength)
+        );
+        assert_eq!(
+            build(&[1u8; 32], &[1u8; 33]).unwrap_err(),
+            Error::Init(InitStage::ValidateRemotePublicKeyLength)
+        );
+        build(&[1u8; 32], &[1u8; 32])?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_builder_reject_weak_psks() -> TestResult {
+        let params: NoiseParams = "Noise_NNpsk0_25519_ChaChaPoly_SHA256".parse()?;
+        assert_eq!(
+            Builder::new(params.clone())
+                .psk(0, &[0u8; 32])?
+                .reject_weak_psks()
+                .build_initiator()
+                .unwrap_err(),
+            Error::Init(InitStage::ValidatePskStrength)
+        );
+        Builder::new(params.clone()).psk(0, &[0u8; 32])?.build_initiator()?;
+
+        let mut psk = [7u8; 32];
+        psk[31] = 8;
+        let mut hs = Builder::new(params).psk(0, &psk)?.reject_weak_psks().build_initiator()?;
+        assert_eq!(hs.set_psk(0, &[0xffu8; 32]), Err(Error::Init(InitStage::ValidatePskStrength)));
+        hs.set_psk(0, &psk)?;
+        Ok(())
+    }
+
     #[test]
     fn test_builder_bad_spec() {
         let params: ::std::result::Result<NoiseParams, _> =
@@ -387,14 +885,70 @@ mod tests {
             build_builder()?.remote_public_key(&[1u8; 32]).unwrap_err(),
             Error::Init(InitStage::ParameterOverwrite)
         );
+        assert_eq!(
+            build_builder()?
+                .rekey_policy(RekeyPolicy::AfterMessages(1))?
+                .rekey_policy(RekeyPolicy::AfterMessages(1))
+                .unwrap_err(),
+            Error::Init(InitStage::ParameterOverwrite)
+        );
+        let dh = DefaultResolver.resolve_dh(&DHChoice::Curve25519).unwrap();
+        assert_eq!(
+            build_builder()?.local_static_dh(dh).unwrap_err(),
+            Error::Init(InitStage::ParameterOverwrite)
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "curve448"))]
+    fn test_builder_local_static_dh_type_mismatch() -> TestResult {
+        let dh = DefaultResolver.resolve_dh(&DHChoice::Curve448).unwrap();
+        assert_eq!(
+            Builder::new("Noise_XX_25519_ChaChaPoly_SHA256".parse()?)
+                .local_static_dh(dh)?
+                .build_initiator()
+                .unwrap_err(),
+            Error::Init(InitStage::ValidateDhTypes)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_max_message_len() -> TestResult {
+        let params: NoiseParams = "Noise_NN_25519_ChaChaPoly_SHA256".parse()?;
+        for len in [0, TAGLEN, MAXMSGLEN + 1] {
+            assert_eq!(
+                Builder::new(params.clone()).max_message_len(len).unwrap_err(),
+                Error::Init(InitStage::ValidateMaxMessageLen)
+            );
+        }
+        assert_eq!(
+            Builder::new(params).max_message_len(1400)?.max_message_len(1400).unwrap_err(),
+            Error::Init(InitStage::ParameterOverwrite)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_rekey_policy_zero() -> TestResult {
+        let params: NoiseParams = "Noise_NN_25519_ChaChaPoly_SHA256".parse()?;
+        assert_eq!(
+            Builder::new(params.clone()).rekey_policy(RekeyPolicy::AfterMessages(0)).unwrap_err(),
+            Error::Init(InitStage::ValidateRekeyPolicy)
+        );
+        assert_eq!(
+            Builder::new(params).rekey_policy(RekeyPolicy::AfterBytes(0)).unwrap_err(),
+            Error::Init(InitStage::ValidateRekeyPolicy)
+        );
         Ok(())
     }
 
     #[test]
     fn test_partialeq_impl() {
-        let keypair_1 = Keypair { private: vec![0x01; 32], public: vec![0x01; 32] };
+        let keypair_1 = Keypair { private: vec![0x01; 32].into(), public: vec![0x01; 32] };
 
-        let mut keypair_2 = Keypair { private: vec![0x01; 32], public: vec![0x01; 32] };
+        let mut keypair_2 = Keypair { private: vec![0x01; 32].into(), public: vec![0x01; 32] };
 
         // If both private and public are the same, return true
         assert!(keypair_1 == keypair_2);
@@ -402,10 +956,10 @@ mod tests {
         // If either public or private are different, return false
 
         // Wrong private
-        keypair_2.private = vec![0x50; 32];
+        keypair_2.private = vec![0x50; 32].into();
         assert!(keypair_1 != keypair_2);
         // Reset to original
-        keypair_2.private = vec![0x01; 32];
+        keypair_2.private = vec![0x01; 32].into();
         // Wrong public
         keypair_2.public = vec![0x50; 32];
         assert!(keypair_1 != keypair_2);
@@ -3,34 +3,94 @@ use crate::{
     error::{Error, InitStage, StateProblem},
     types::Cipher,
 };
-
-pub(crate) struct CipherState {
+use alloc::boxed::Box;
+
+/// A Noise `CipherState`: a cipher key together with the nonce that is incremented for every
+/// message, see Section 5.1 of the Noise Specification.
+///
+/// Snow creates these internally for handshakes and transport states, but one can also be used
+/// on its own, e.g. to encrypt records under a key derived elsewhere.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{params::CipherChoice, resolvers::{CryptoResolver, DefaultResolver}, CipherState};
+/// #
+/// # #[cfg(feature = "default-resolver")]
+/// # fn try_main() -> Result<(), snow::Error> {
+/// let cipher = || DefaultResolver.resolve_cipher(&CipherChoice::ChaChaPoly).unwrap();
+/// let mut sender = CipherState::with_key(cipher(), &[7u8; 32]);
+/// let mut receiver = CipherState::with_key(cipher(), &[7u8; 32]);
+///
+/// let (mut record, mut plaintext) = ([0u8; 64], [0u8; 64]);
+/// let len = sender.encrypt_with_ad(b"header", b"hello", &mut record)?;
+/// let len = receiver.decrypt_with_ad(b"header", &record[..len], &mut plaintext)?;
+/// assert_eq!(&plaintext[..len], b"hello");
+/// assert_eq!(receiver.nonce(), 1);
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "default-resolver"))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+pub struct CipherState {
     cipher:  Box<dyn Cipher>,
     n:       u64,
     has_key: bool,
 }
 
 impl CipherState {
-    pub fn new(cipher: Box<dyn Cipher>) -> Self {
+    pub(crate) fn new(cipher: Box<dyn Cipher>) -> Self {
         Self { cipher, n: 0, has_key: false }
     }
 
-    pub fn name(&self) -> &'static str {
+    /// Create a `CipherState` that encrypts with `cipher` under `key`, starting at nonce 0.
+    ///
+    /// The cipher can be obtained from a resolver, e.g.
+    /// `DefaultResolver.resolve_cipher(&CipherChoice::ChaChaPoly)`.
+    #[must_use]
+    pub fn with_key(cipher: Box<dyn Cipher>, key: &[u8; CIPHERKEYLEN]) -> Self {
+        let mut state = Self::new(cipher);
+        state.set(key, 0);
+        state
+    }
+
+    pub(crate) fn into_cipher(self) -> Box<dyn Cipher> {
+        self.cipher
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
         self.cipher.name()
     }
 
-    pub fn set(&mut self, key: &[u8; CIPHERKEYLEN], n: u64) {
+    pub(crate) fn set(&mut self, key: &[u8; CIPHERKEYLEN], n: u64) {
         self.cipher.set(key);
         self.n = n;
         self.has_key = true;
     }
 
-    pub fn encrypt_ad(
+    /// Encrypt `plaintext` with the associated data `authtext` into `out` using the current
+    /// nonce, then increment the nonce. Returns the number of bytes written, which is
+    /// `plaintext.len()` plus the 16-byte tag.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `out` is too small, and `StateProblem::Exhausted` if
+    /// the nonce has reached its maximum.
+    pub fn encrypt_with_ad(
         &mut self,
         authtext: &[u8],
         plaintext: &[u8],
         out: &mut [u8],
     ) -> Result<usize, Error> {
+        if out.len() < plaintext.len() + TAGLEN {
+            return Err(Error::Input);
+        }
+
         if !self.has_key {
             return Err(StateProblem::MissingKeyMaterial.into());
         }
@@ -44,14 +104,24 @@ impl CipherState {
         Ok(len)
     }
 
-    pub fn decrypt_ad(
+    /// Decrypt `ciphertext` with the associated data `authtext` into `out` using the current
+    /// nonce, then increment the nonce. Returns the number of plaintext bytes.
+    ///
+    /// The nonce is left unchanged if decryption fails, so a forged message doesn't
+    /// desynchronise the state.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Decrypt` if `ciphertext` fails authentication or `out` is too
+    /// small, and `StateProblem::Exhausted` if the nonce has reached its maximum.
+    pub fn decrypt_with_ad(
         &mut self,
         authtext: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
     ) -> Result<usize, Error> {
         if (ciphertext.len() < TAGLEN) || out.len() < (ciphertext.len() - TAGLEN) {
-            return Err(Error::Decrypt);
+            return Err(Error::Decrypt { token: None });
         }
 
         if !self.has_key {
@@ -67,26 +137,79 @@ impl CipherState {
         Ok(len)
     }
 
-    pub fn encrypt(&mut self, plaintext: &[u8], out: &mut [u8]) -> Result<usize, Error> {
-        self.encrypt_ad(&[0u8; 0], plaintext, out)
+    pub(crate) fn encrypt_ad_in_place(
+        &mut self,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> Result<usize, Error> {
+        if in_out.len() < plaintext_len + TAGLEN {
+            return Err(Error::Input);
+        }
+
+        if !self.has_key {
+            return Err(StateProblem::MissingKeyMaterial.into());
+        }
+
+        validate_nonce(self.n)?;
+        let len = self.cipher.encrypt_in_place(self.n, authtext, in_out, plaintext_len);
+
+        // We have validated this will not wrap around.
+        self.n += 1;
+
+        Ok(len)
+    }
+
+    pub(crate) fn decrypt_ad_in_place(
+        &mut self,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if in_out.len() < TAGLEN {
+            return Err(Error::Decrypt { token: None });
+        }
+
+        if !self.has_key {
+            return Err(StateProblem::MissingKeyMaterial.into());
+        }
+
+        validate_nonce(self.n)?;
+        let len = self.cipher.decrypt_in_place(self.n, authtext, in_out)?;
+
+        // We have validated this will not wrap around.
+        self.n += 1;
+
+        Ok(len)
+    }
+
+    pub(crate) fn encrypt(&mut self, plaintext: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.encrypt_with_ad(&[0u8; 0], plaintext, out)
     }
 
-    pub fn decrypt(&mut self, ciphertext: &[u8], out: &mut [u8]) -> Result<usize, Error> {
-        self.decrypt_ad(&[0u8; 0], ciphertext, out)
+    pub(crate) fn decrypt(&mut self, ciphertext: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.decrypt_with_ad(&[0u8; 0], ciphertext, out)
     }
 
+    /// Replace the key with a new one derived from it, as `REKEY()` in Section 4.2 of the Noise
+    /// Specification. The nonce is left unchanged.
     pub fn rekey(&mut self) {
         self.cipher.rekey();
     }
 
-    pub fn rekey_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
+    pub(crate) fn rekey_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
         self.cipher.set(key);
     }
 
+    /// The nonce the next message will be encrypted or decrypted with.
+    #[must_use]
     pub fn nonce(&self) -> u64 {
         self.n
     }
 
+    /// Set the nonce the next message will be encrypted or decrypted with, e.g. to skip over
+    /// lost messages.
+    ///
+    /// Never reuse a nonce for encryption under the same key.
     pub fn set_nonce(&mut self, nonce: u64) {
         self.n = nonce;
     }
@@ -150,7 +273,7 @@ impl StatelessCipherState {
         out: &mut [u8],
     ) -> Result<usize, Error> {
         if (ciphertext.len() < TAGLEN) || out.len() < (ciphertext.len() - TAGLEN) {
-            return Err(Error::Decrypt);
+            return Err(Error::Decrypt { token: None });
         }
 
         if !self.has_key {
@@ -162,6 +285,45 @@ impl StatelessCipherState {
         self.cipher.decrypt(nonce, authtext, ciphertext, out)
     }
 
+    pub fn encrypt_ad_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> Result<usize, Error> {
+        if in_out.len() < plaintext_len + TAGLEN {
+            return Err(Error::Input);
+        }
+
+        if !self.has_key {
+            return Err(StateProblem::MissingKeyMaterial.into());
+        }
+
+        validate_nonce(nonce)?;
+
+        Ok(self.cipher.encrypt_in_place(nonce, authtext, in_out, plaintext_len))
+    }
+
+    pub fn decrypt_ad_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if in_out.len() < TAGLEN {
+            return Err(Error::Decrypt { token: None });
+        }
+
+        if !self.has_key {
+            return Err(StateProblem::MissingKeyMaterial.into());
+        }
+
+        validate_nonce(nonce)?;
+
+        self.cipher.decrypt_in_place(nonce, authtext, in_out)
+    }
+
     pub fn encrypt(&self, nonce: u64, plaintext: &[u8], out: &mut [u8]) -> Result<usize, Error> {
         self.encrypt_ad(nonce, &[], plaintext, out)
     }
@@ -3,7 +3,10 @@ pub const CIPHERKEYLEN: usize = 32;
 pub const TAGLEN: usize = 16;
 
 pub const MAXHASHLEN: usize = 64;
+#[cfg(not(feature = "sha3"))]
 pub const MAXBLOCKLEN: usize = 128;
+#[cfg(feature = "sha3")]
+pub const MAXBLOCKLEN: usize = 136;
 pub const MAXDHLEN: usize = 56;
 pub const MAXMSGLEN: usize = 65535;
 
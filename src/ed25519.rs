@@ -0,0 +1,98 @@
+//! Use an Ed25519 identity key as the static key of a `25519` handshake.
+//!
+//! The conversions are the birational map between the Edwards and Montgomery forms of
+//! Curve25519, as in libsodium's `crypto_sign_ed25519_pk_to_curve25519` and
+//! `crypto_sign_ed25519_sk_to_curve25519`, so they interoperate with keys converted there.
+//!
+//! # Examples
+//!
+//! ```
+//! # use snow::{ed25519, Builder};
+//! #
+//! # #[cfg(feature = "std")]
+//! # fn try_main() -> Result<(), snow::Error> {
+//! # let my_ed25519_seed = [1u8; 32];
+//! # let their_ed25519_seed = [2u8; 32];
+//! # let their_ed25519_public = ed25519::public_key(&their_ed25519_seed);
+//! let params: snow::params::NoiseParams = "Noise_KK_25519_ChaChaPoly_BLAKE2s".parse()?;
+//! let local = ed25519::x25519_keypair(&my_ed25519_seed);
+//! let remote = ed25519::x25519_public_key(&their_ed25519_public)?;
+//! let initiator = Builder::new(params)
+//!     .local_keypair(&local)?
+//!     .remote_public_key(&remote)?
+//!     .build_initiator()?;
+//! #     Ok(())
+//! # }
+//! #
+//! # #[cfg(not(feature = "std"))]
+//! # fn try_main() -> Result<(), ()> { Ok(()) }
+//! #
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use crate::{builder::Keypair, error::Error};
+use curve25519_dalek::{
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    montgomery::MontgomeryPoint,
+};
+use sha2::{Digest, Sha512};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// The X25519 private scalar of an Ed25519 secret key, i.e. the first half of
+/// `SHA-512(seed)`, clamped.
+fn x25519_scalar(ed25519_seed: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+    let mut hash = Zeroizing::new([0u8; 64]);
+    hash.copy_from_slice(&Sha512::digest(ed25519_seed));
+    let mut scalar = Zeroizing::new([0u8; 32]);
+    scalar.copy_from_slice(&hash[..32]);
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    scalar
+}
+
+/// The Ed25519 public key of the secret key `ed25519_seed` (the 32-byte secret key of
+/// RFC 8032).
+#[must_use]
+pub fn public_key(ed25519_seed: &[u8; 32]) -> [u8; 32] {
+    EdwardsPoint::mul_base_clamped(*x25519_scalar(ed25519_seed)).compress().to_bytes()
+}
+
+/// The X25519 keypair corresponding to the Ed25519 secret key `ed25519_seed` (the 32-byte
+/// secret key of RFC 8032), for use as a local static key.
+#[must_use]
+pub fn x25519_keypair(ed25519_seed: &[u8; 32]) -> Keypair {
+    let scalar = x25519_scalar(ed25519_seed);
+    let public = MontgomeryPoint::mul_base_clamped(*scalar).to_bytes().to_vec();
+    Keypair { private: Zeroizing::new(scalar.to_vec()), public }
+}
+
+/// The X25519 public key corresponding to the Ed25519 public key `ed25519_public`, for use
+/// as a remote static key.
+///
+/// # Errors
+///
+/// Will result in `Error::Input` if `ed25519_public` isn't a valid point, or is of small
+/// order (which would make every DH with it predictable).
+pub fn x25519_public_key(ed25519_public: &[u8; 32]) -> Result<[u8; 32], Error> {
+    let point = CompressedEdwardsY(*ed25519_public).decompress().ok_or(Error::Input)?;
+    if point.is_small_order() {
+        return Err(Error::Input);
+    }
+    Ok(point.to_montgomery().to_bytes())
+}
+
+/// Check that `x25519_public`, e.g. the remote static key learned in a handshake, belongs to
+/// the identity `ed25519_public`.
+///
+/// The comparison is constant-time.
+#[must_use]
+pub fn x25519_matches(ed25519_public: &[u8; 32], x25519_public: &[u8]) -> bool {
+    match x25519_public_key(ed25519_public) {
+        Ok(expected) => expected[..].ct_eq(x25519_public).into(),
+        Err(_) => false,
+    }
+}
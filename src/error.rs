@@ -1,6 +1,7 @@
 //! All error types used by Snow operations.
 
-use std::fmt;
+use crate::params::{DhToken, Token};
+use core::fmt;
 
 /// `snow` provides decently detailed errors, exposed as the [`Error`] enum,
 /// to allow developers to react to errors in a more actionable way.
@@ -34,10 +35,21 @@ pub enum Error {
     Input,
 
     /// Diffie-Hellman agreement failed.
-    Dh,
+    Dh {
+        /// The handshake token the DH was for, or `None` outside of a handshake message.
+        token: Option<DhToken>,
+    },
 
     /// Decryption failed.
-    Decrypt,
+    Decrypt {
+        /// The handshake token that didn't decrypt, e.g. `S` for a remote static key, or
+        /// `None` for a payload or a transport message. A payload that doesn't decrypt after
+        /// a DH usually means the peers disagree on a key, not on the payload.
+        token: Option<Token>,
+    },
+
+    /// The peers have no protocol in common, see `params::SupportedProtocols`.
+    Negotiation,
 
     /// Key-encapsulation failed
     #[cfg(feature = "hfs")]
@@ -79,6 +91,18 @@ pub enum PatternProblem {
     UnsupportedKemType,
 }
 
+impl Error {
+    /// Note the handshake token `token` in a DH or decrypt error that happened while
+    /// processing it.
+    pub(crate) fn at_token(self, token: Option<Token>) -> Self {
+        match (self, token) {
+            (Error::Dh { token: None }, Some(Token::Dh(token))) => Error::Dh { token: Some(token) },
+            (Error::Decrypt { token: None }, Some(token)) => Error::Decrypt { token: Some(token) },
+            (err, _) => err,
+        }
+    }
+}
+
 impl From<PatternProblem> for Error {
     fn from(reason: PatternProblem) -> Self {
         Error::Pattern(reason)
@@ -93,6 +117,15 @@ pub enum InitStage {
     ValidateKeyLengths,
     /// Provided and received preshared key lengths were not equal.
     ValidatePskLengths,
+    /// A PSK was a single repeated byte (e.g. all zeros) while `Builder::reject_weak_psks()`
+    /// was in effect.
+    ValidatePskStrength,
+    /// The key given to `Builder::local_private_key()` (or as the fixed ephemeral key) isn't
+    /// the private key length of the DH algorithm named in the params.
+    ValidatePrivateKeyLength,
+    /// The key given to `Builder::remote_public_key()` isn't the public key length of the DH
+    /// algorithm named in the params.
+    ValidateRemotePublicKeyLength,
     /// Two separate cipher algorithms were initialized.
     ValidateCipherTypes,
     /// The RNG couldn't be initialized.
@@ -111,6 +144,17 @@ pub enum InitStage {
     /// The Builder already has set a value for this parameter, and overwrites are not allowed as
     /// they can introduce subtle security issues.
     ParameterOverwrite,
+    /// The rekey policy's threshold is zero.
+    ValidateRekeyPolicy,
+    /// The maximum message length given to `Builder::max_message_len()` is not larger than
+    /// the AEAD tag, or exceeds the Noise limit of 65535 bytes.
+    ValidateMaxMessageLen,
+    /// The static key given to `Builder::local_static_dh()` doesn't use the DH algorithm (or
+    /// key length) named in the params.
+    ValidateDhTypes,
+    /// The parameters given for a fallback handshake don't use the `fallback` modifier, or
+    /// don't match the primitives of the handshake being replaced.
+    ValidateFallback,
 }
 
 impl From<InitStage> for Error {
@@ -151,8 +195,22 @@ pub enum StateProblem {
     HandshakeAlreadyFinished,
     /// You called a method that is only valid if this weren't a one-way handshake.
     OneWay,
+    /// You called a method that isn't valid in half-duplex mode.
+    HalfDuplex,
+    /// The handshake given to `TransportState::complete_rehandshake()` wasn't built with
+    /// `Builder::bind_to_session()` for that session.
+    NotBoundToSession,
     /// The nonce counter attempted to go higher than (2^64) - 1
+    ///
+    /// The counter is left at its maximum, so every later message in that direction fails
+    /// the same way and the session has to be re-established.
     Exhausted,
+    /// The message's nonce was already received, or is too old to tell, according to a
+    /// `ReplayWindow`.
+    Replayed,
+    /// The session was closed in this direction with `TransportState::write_close()`, either
+    /// by this side (when writing) or by the peer (when reading).
+    Closed,
 }
 
 impl From<StateProblem> for Error {
@@ -173,12 +231,16 @@ impl fmt::Display for Error {
             },
             Error::State(reason) => write!(f, "state error: {reason:?}"),
             Error::Input => write!(f, "input error"),
-            Error::Dh => write!(f, "diffie-hellman error"),
-            Error::Decrypt => write!(f, "decrypt error"),
+            Error::Dh { token: None } => write!(f, "diffie-hellman error"),
+            Error::Dh { token: Some(token) } => write!(f, "diffie-hellman error at {token}"),
+            Error::Decrypt { token: None } => write!(f, "decrypt error"),
+            Error::Decrypt { token: Some(token) } => write!(f, "decrypt error at {token}"),
+            Error::Negotiation => write!(f, "no mutually supported protocol"),
             #[cfg(feature = "hfs")]
             Error::Kem => write!(f, "kem error"),
         }
     }
 }
 
+#[cfg(any(feature = "std", test))]
 impl std::error::Error for Error {}
@@ -1,58 +1,88 @@
 #[cfg(feature = "risky-raw-split")]
-use crate::constants::{CIPHERKEYLEN, MAXHASHLEN};
+use crate::constants::CIPHERKEYLEN;
 #[cfg(feature = "hfs")]
 use crate::constants::{MAXKEMCTLEN, MAXKEMPUBLEN, MAXKEMSSLEN};
 #[cfg(feature = "hfs")]
 use crate::types::Kem;
 use crate::{
+    builder::Keypair,
     cipherstate::{CipherState, CipherStates},
-    constants::{MAXDHLEN, MAXMSGLEN, PSKLEN, TAGLEN},
-    error::{Error, InitStage, StateProblem},
-    params::{DhToken, HandshakeTokens, MessagePatterns, NoiseParams, Token},
+    constants::{MAXDHLEN, MAXHASHLEN, MAXMSGLEN, PSKLEN, TAGLEN},
+    error::{Error, InitStage, Prerequisite, StateProblem},
+    observer::Observer,
+    params::{
+        payload_security, AuthLevel, ConfLevel, DhToken, HandshakeTokens, MessagePatterns,
+        NoiseParams, Token,
+    },
     stateless_transportstate::StatelessTransportState,
-    symmetricstate::SymmetricState,
-    transportstate::TransportState,
-    types::{Dh, Hash, Random},
+    symmetricstate::{SymmetricState, SymmetricStateData},
+    transportstate::{RekeyPolicy, TransportState},
+    types::{Dh, Random},
     utils::Toggle,
 };
-use std::{
+use alloc::{boxed::Box, vec::Vec};
+use core::{
     convert::{TryFrom, TryInto},
     fmt,
 };
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
 /// A state machine encompassing the handshake phase of a Noise session.
 ///
 /// **Note:** you are probably looking for [`Builder`](struct.Builder.html) to
 /// get started.
 ///
+/// Nothing is done for the next message until it's written, so after a successful
+/// [`read_message()`](Self::read_message) the received payload and, if the message carried
+/// one, the remote static key ([`get_remote_static()`](Self::get_remote_static)) can be
+/// inspected before deciding whether to respond. To reject the peer, drop the state: all of
+/// its key material is zeroized on drop, and nothing was sent that commits to a response.
+///
 /// See: <https://noiseprotocol.org/noise.html#the-handshakestate-object>
 pub struct HandshakeState {
-    pub(crate) rng:              Box<dyn Random>,
-    pub(crate) symmetricstate:   SymmetricState,
-    pub(crate) cipherstates:     CipherStates,
-    pub(crate) s:                Toggle<Box<dyn Dh>>,
-    pub(crate) e:                Toggle<Box<dyn Dh>>,
-    pub(crate) fixed_ephemeral:  bool,
-    pub(crate) rs:               Toggle<[u8; MAXDHLEN]>,
-    pub(crate) re:               Toggle<[u8; MAXDHLEN]>,
-    pub(crate) initiator:        bool,
-    pub(crate) params:           NoiseParams,
-    pub(crate) psks:             [Option<[u8; PSKLEN]>; 10],
+    pub(crate) rng:                    Box<dyn Random>,
+    pub(crate) symmetricstate:         SymmetricState,
+    pub(crate) cipherstates:           CipherStates,
+    pub(crate) s:                      Toggle<Box<dyn Dh>>,
+    /// Whether `s` was given to `Builder::local_static_dh()`, so its private key can't be
+    /// read or set.
+    pub(crate) external_s:             bool,
+    pub(crate) e:                      Toggle<Box<dyn Dh>>,
+    pub(crate) fixed_ephemeral:        bool,
+    pub(crate) rs:                     Toggle<[u8; MAXDHLEN]>,
+    pub(crate) re:                     Toggle<[u8; MAXDHLEN]>,
+    pub(crate) initiator:              bool,
+    pub(crate) params:                 NoiseParams,
+    pub(crate) psks:                   Zeroizing<[Option<[u8; PSKLEN]>; 10]>,
     #[cfg(feature = "hfs")]
-    pub(crate) kem:              Option<Box<dyn Kem>>,
+    pub(crate) kem:                    Option<Box<dyn Kem>>,
     #[cfg(feature = "hfs")]
-    pub(crate) kem_re:           Option<[u8; MAXKEMPUBLEN]>,
-    pub(crate) my_turn:          bool,
-    pub(crate) message_patterns: MessagePatterns,
-    pub(crate) pattern_position: usize,
+    pub(crate) kem_re:                 Option<[u8; MAXKEMPUBLEN]>,
+    pub(crate) my_turn:                bool,
+    pub(crate) message_patterns:       MessagePatterns,
+    pub(crate) pattern_position:       usize,
+    /// The token being processed, so that a DH or decrypt error can name it.
+    pub(crate) current_token:          Option<Token>,
+    pub(crate) rekey_policy:           Option<RekeyPolicy>,
+    /// The handshake hash of the session given to `Builder::bind_to_session()`.
+    pub(crate) bound_hash:             Option<Zeroizing<Vec<u8>>>,
+    pub(crate) max_message_len:        usize,
+    pub(crate) reject_weak_psks:       bool,
+    pub(crate) reject_zero_dh:         bool,
+    pub(crate) observer:               Observer,
+    /// The symmetric state before the pre-messages, so they can be mixed in again with
+    /// another local static key.
+    pub(crate) initial_symmetricstate: Zeroizing<SymmetricStateData>,
 }
 
 impl HandshakeState {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         rng: Box<dyn Random>,
-        cipherstate: CipherState,
-        hasher: Box<dyn Hash>,
+        mut symmetricstate: SymmetricState,
         s: Toggle<Box<dyn Dh>>,
         e: Toggle<Box<dyn Dh>>,
         fixed_ephemeral: bool,
@@ -73,74 +103,23 @@ impl HandshakeState {
 
         let tokens = HandshakeTokens::try_from(&params.handshake)?;
 
-        let mut symmetricstate = SymmetricState::new(cipherstate, hasher);
-
         symmetricstate.initialize(&params.name);
         symmetricstate.mix_hash(prologue);
 
-        let dh_len = s.pub_len();
-        if initiator {
-            for token in tokens.premsg_pattern_i {
-                symmetricstate.mix_hash(
-                    match *token {
-                        Token::S => &s,
-                        Token::E => &e,
-                        _ => unreachable!(),
-                    }
-                    .get()
-                    .ok_or(StateProblem::MissingKeyMaterial)?
-                    .pubkey(),
-                );
-            }
-            for token in tokens.premsg_pattern_r {
-                symmetricstate.mix_hash(
-                    &match *token {
-                        Token::S => &rs,
-                        Token::E => &re,
-                        _ => unreachable!(),
-                    }
-                    .get()
-                    .ok_or(StateProblem::MissingKeyMaterial)?[..dh_len],
-                );
-            }
-        } else {
-            for token in tokens.premsg_pattern_i {
-                symmetricstate.mix_hash(
-                    &match *token {
-                        Token::S => &rs,
-                        Token::E => &re,
-                        _ => unreachable!(),
-                    }
-                    .get()
-                    .ok_or(StateProblem::MissingKeyMaterial)?[..dh_len],
-                );
-            }
-            for token in tokens.premsg_pattern_r {
-                symmetricstate.mix_hash(
-                    match *token {
-                        Token::S => &s,
-                        Token::E => &e,
-                        _ => unreachable!(),
-                    }
-                    .get()
-                    .ok_or(StateProblem::MissingKeyMaterial)?
-                    .pubkey(),
-                );
-            }
-        }
-
-        Ok(HandshakeState {
+        let initial_symmetricstate = symmetricstate.checkpoint();
+        let mut hs = HandshakeState {
             rng,
             symmetricstate,
             cipherstates,
             s,
+            external_s: false,
             e,
             fixed_ephemeral,
             rs,
             re,
             initiator,
             params,
-            psks: *psks,
+            psks: Zeroizing::new(*psks),
             #[cfg(feature = "hfs")]
             kem: None,
             #[cfg(feature = "hfs")]
@@ -148,7 +127,41 @@ impl HandshakeState {
             my_turn: initiator,
             message_patterns: tokens.msg_patterns,
             pattern_position: 0,
-        })
+            current_token: None,
+            rekey_policy: None,
+            bound_hash: None,
+            max_message_len: MAXMSGLEN,
+            reject_weak_psks: false,
+            reject_zero_dh: false,
+            observer: Observer::default(),
+            initial_symmetricstate,
+        };
+        hs.mix_premessages(tokens.premsg_pattern_i, tokens.premsg_pattern_r)?;
+        Ok(hs)
+    }
+
+    /// Mix the public keys of the initiator's and then the responder's pre-message into the
+    /// symmetric state.
+    fn mix_premessages(&mut self, premsg_i: &[Token], premsg_r: &[Token]) -> Result<(), Error> {
+        let dh_len = self.dh_len();
+        let is_psk = self.params.handshake.is_psk();
+        for (premsg, local) in [(premsg_i, self.initiator), (premsg_r, !self.initiator)] {
+            for &token in premsg {
+                let key = match (token, local) {
+                    (Token::S, true) => self.s.get().map(|s| s.pubkey()),
+                    (Token::E, true) => self.e.get().map(|e| e.pubkey()),
+                    (Token::S, false) => self.rs.get().map(|rs| &rs[..dh_len]),
+                    (Token::E, false) => self.re.get().map(|re| &re[..dh_len]),
+                    _ => unreachable!(),
+                }
+                .ok_or(StateProblem::MissingKeyMaterial)?;
+                self.symmetricstate.mix_hash(key);
+                if is_psk && token == Token::E {
+                    self.symmetricstate.mix_key(key);
+                }
+            }
+        }
+        Ok(())
     }
 
     pub(crate) fn dh_len(&self) -> usize {
@@ -160,8 +173,8 @@ impl HandshakeState {
         self.kem = Some(kem);
     }
 
-    fn dh(&self, token: DhToken) -> Result<[u8; MAXDHLEN], Error> {
-        let mut dh_out = [0u8; MAXDHLEN];
+    fn dh(&self, token: DhToken) -> Result<Zeroizing<[u8; MAXDHLEN]>, Error> {
+        let mut dh_out = Zeroizing::new([0u8; MAXDHLEN]);
         let (dh, key) = match (token, self.is_initiator()) {
             (DhToken::Ee, _) => (&self.e, &self.re),
             (DhToken::Ss, _) => (&self.s, &self.rs),
@@ -171,7 +184,11 @@ impl HandshakeState {
         if !(dh.is_on() && key.is_on()) {
             return Err(StateProblem::MissingKeyMaterial.into());
         }
-        dh.dh(&**key, &mut dh_out)?;
+        dh.dh(&**key, &mut *dh_out)?;
+        let len = dh.pub_len();
+        if self.reject_zero_dh && bool::from(dh_out[..len].ct_eq(&[0u8; MAXDHLEN][..len])) {
+            return Err(Error::Dh { token: None });
+        }
         Ok(dh_out)
     }
 
@@ -203,23 +220,90 @@ impl HandshakeState {
     /// # Errors
     ///
     /// Will result in `Error::Input` if the size of the output exceeds the max message
-    /// length in the Noise Protocol (65535 bytes).
+    /// length in the Noise Protocol (65535 bytes), or the one set with
+    /// `Builder::max_message_len()`.
     pub fn write_message(&mut self, payload: &[u8], message: &mut [u8]) -> Result<usize, Error> {
         let checkpoint = self.symmetricstate.checkpoint();
         match self._write_message(payload, message) {
             Ok(res) => {
                 self.pattern_position += 1;
                 self.my_turn = false;
+                self.observer.message_written(res);
                 Ok(res)
             },
             Err(err) => {
                 self.symmetricstate.restore(checkpoint);
-                Err(err)
+                Err(err.at_token(self.current_token.take()))
             },
         }
     }
 
     fn _write_message(&mut self, payload: &[u8], message: &mut [u8]) -> Result<usize, Error> {
+        let mut byte_index = self.write_tokens(message)?;
+        if byte_index + payload.len() + TAGLEN > message.len() {
+            return Err(Error::Input);
+        }
+        byte_index +=
+            self.symmetricstate.encrypt_and_mix_hash(payload, &mut message[byte_index..])?;
+        self.finish_write(byte_index)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is taken from
+    /// `message[..payload_len]` and encrypted in place, so no separate payload buffer is needed.
+    ///
+    /// Returns the number of bytes written to `message`. On error the contents of `message`
+    /// are unspecified.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` is too small to hold the handshake tokens,
+    /// the payload and its tag, or if the output exceeds the max message length in the Noise
+    /// Protocol (65535 bytes), or the one set with `Builder::max_message_len()`.
+    pub fn write_message_in_place(
+        &mut self,
+        message: &mut [u8],
+        payload_len: usize,
+    ) -> Result<usize, Error> {
+        let checkpoint = self.symmetricstate.checkpoint();
+        match self._write_message_in_place(message, payload_len) {
+            Ok(res) => {
+                self.pattern_position += 1;
+                self.my_turn = false;
+                self.observer.message_written(res);
+                Ok(res)
+            },
+            Err(err) => {
+                self.symmetricstate.restore(checkpoint);
+                Err(err.at_token(self.current_token.take()))
+            },
+        }
+    }
+
+    fn _write_message_in_place(
+        &mut self,
+        message: &mut [u8],
+        payload_len: usize,
+    ) -> Result<usize, Error> {
+        if payload_len > self.max_message_len || payload_len > message.len() {
+            return Err(Error::Input);
+        }
+
+        // Park the payload at the end of the buffer while the tokens are written in front of it.
+        let tail = message.len() - payload_len;
+        message.copy_within(..payload_len, tail);
+        let mut byte_index = self.write_tokens(&mut message[..tail])?;
+        if byte_index + payload_len + TAGLEN > message.len() {
+            return Err(Error::Input);
+        }
+        message.copy_within(tail.., byte_index);
+        byte_index += self
+            .symmetricstate
+            .encrypt_and_mix_hash_in_place(&mut message[byte_index..], payload_len)?;
+        self.finish_write(byte_index)
+    }
+
+    /// Write the tokens of the current message pattern, returning the number of bytes written.
+    fn write_tokens(&mut self, message: &mut [u8]) -> Result<usize, Error> {
         if !self.my_turn {
             return Err(StateProblem::NotTurnToWrite.into());
         } else if self.pattern_position >= self.message_patterns.len() {
@@ -228,6 +312,7 @@ impl HandshakeState {
 
         let mut byte_index = 0;
         for token in &self.message_patterns[self.pattern_position] {
+            self.current_token = Some(*token);
             match *token {
                 Token::E => {
                     if byte_index + self.e.pub_len() > message.len() {
@@ -258,8 +343,8 @@ impl HandshakeState {
                         .encrypt_and_mix_hash(self.s.pubkey(), &mut message[byte_index..])?;
                 },
                 Token::Psk(n) => match self.psks[n as usize] {
-                    Some(psk) => {
-                        self.symmetricstate.mix_key_and_hash(&psk);
+                    Some(ref psk) => {
+                        self.symmetricstate.mix_key_and_hash(psk);
                     },
                     None => {
                         return Err(StateProblem::MissingPsk.into());
@@ -306,13 +391,13 @@ impl HandshakeState {
                 },
             }
         }
+        self.current_token = None;
 
-        if byte_index + payload.len() + TAGLEN > message.len() {
-            return Err(Error::Input);
-        }
-        byte_index +=
-            self.symmetricstate.encrypt_and_mix_hash(payload, &mut message[byte_index..])?;
-        if byte_index > MAXMSGLEN {
+        Ok(byte_index)
+    }
+
+    fn finish_write(&mut self, byte_index: usize) -> Result<usize, Error> {
+        if byte_index > self.max_message_len {
             return Err(Error::Input);
         }
         if self.pattern_position == (self.message_patterns.len() - 1) {
@@ -321,10 +406,34 @@ impl HandshakeState {
         Ok(byte_index)
     }
 
+    /// Like [`write_message`](Self::write_message), but the payload is the concatenation of
+    /// the `payload` fragments, which are encrypted as a single Noise message without first
+    /// being joined into a separate buffer.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` can't hold the payload and the rest of the
+    /// message, or if the size of the output exceeds the max message length in the Noise
+    /// Protocol (65535 bytes), or the one set with `Builder::max_message_len()`.
+    #[cfg(feature = "std")]
+    pub fn write_message_vectored(
+        &mut self,
+        payload: &[IoSlice<'_>],
+        message: &mut [u8],
+    ) -> Result<usize, Error> {
+        let payload_len = crate::utils::gather(payload, message)?;
+        self.write_message_in_place(message, payload_len)
+    }
+
     /// Read a noise message from `message` and write the payload to the `payload` buffer.
     ///
     /// Returns the number of bytes written to `payload`.
     ///
+    /// A failed read leaves the handshake as it was before the call, so a garbled or forged
+    /// message can be dropped and the handshake continued with the next one received.
+    ///
     /// # Errors
     ///
     /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
@@ -332,33 +441,159 @@ impl HandshakeState {
     ///
     /// Will result in `StateProblem::Exhausted` if the max nonce count overflows.
     pub fn read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
+        self.read_atomically(message.len(), |hs| hs._read_message(message, payload))
+    }
+
+    fn _read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
+        let ptr = &message[self.read_tokens(message)?..];
+        self.symmetricstate.decrypt_and_mix_hash(ptr, payload)?;
+        self.finish_read();
+        let payload_len = ptr.len() - if self.symmetricstate.has_key() { TAGLEN } else { 0 };
+        Ok(payload_len)
+    }
+
+    /// Like [`read_message`](Self::read_message), but the payload is decrypted in place and
+    /// moved to the start of `message`, so no separate payload buffer is needed.
+    ///
+    /// Returns the length of the payload in `message`. On error the contents of `message`
+    /// are unspecified.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
+    /// authentication tag didn't verify.
+    ///
+    /// Will result in `StateProblem::Exhausted` if the max nonce count overflows.
+    pub fn read_message_in_place(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        let len = message.len();
+        self.read_atomically(len, |hs| hs._read_message_in_place(message))
+    }
+
+    /// Like [`read_message`](Self::read_message) for the first message of the handshake, but
+    /// tries each of `keypairs` in turn as the local static keypair, e.g. so that a responder
+    /// rotating its static key accepts `IK` initiators that encrypted to either the old or the
+    /// new one.
+    ///
+    /// Returns the length of the payload and the index in `keypairs` of the keypair that read
+    /// the message, which stays the local static keypair for the rest of the handshake. Since a
+    /// static key in the pre-message is part of the handshake hash, each attempt starts over
+    /// from the pre-messages with its keypair, and is rolled back like a failed
+    /// `read_message()` if it fails. If no keypair works, the local static keypair is put back
+    /// and the error of the last attempt is returned.
+    ///
+    /// Every attempt repeats the DH calculations of the message, so list the most likely
+    /// keypair first.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Prerequisite::LocalPrivateKey` if this side has no static key, and in
+    /// `Error::Input` if this isn't the first message, if the static key was given to
+    /// [`Builder::local_static_dh()`](crate::Builder::local_static_dh), if `keypairs` is empty
+    /// or if one of their private keys isn't the private key length of the DH algorithm.
+    /// Otherwise, as [`read_message`](Self::read_message).
+    pub fn read_message_with_static_keys(
+        &mut self,
+        message: &[u8],
+        payload: &mut [u8],
+        keypairs: &[Keypair],
+    ) -> Result<(usize, usize), Error> {
+        let priv_len = self.s.priv_len();
+        if !self.s.is_on() {
+            return Err(Prerequisite::LocalPrivateKey.into());
+        } else if self.pattern_position != 0
+            || self.external_s
+            || keypairs.is_empty()
+            || keypairs.iter().any(|keypair| keypair.private.len() != priv_len)
+        {
+            return Err(Error::Input);
+        }
+
+        let mut original = Zeroizing::new([0u8; MAXDHLEN]);
+        original[..priv_len].copy_from_slice(self.s.privkey());
         let checkpoint = self.symmetricstate.checkpoint();
-        match self._read_message(message, payload) {
+        let result = self.try_static_keys(message, payload, keypairs);
+        if result.is_err() {
+            self.s.set(&original[..priv_len]);
+            self.symmetricstate.restore(checkpoint);
+        }
+        result
+    }
+
+    fn try_static_keys(
+        &mut self,
+        message: &[u8],
+        payload: &mut [u8],
+        keypairs: &[Keypair],
+    ) -> Result<(usize, usize), Error> {
+        let tokens = HandshakeTokens::try_from(&self.params.handshake)?;
+        let mut last_error = Error::Input;
+        for (index, keypair) in keypairs.iter().enumerate() {
+            self.s.set(&keypair.private);
+            self.symmetricstate.restore(self.initial_symmetricstate.clone());
+            self.mix_premessages(tokens.premsg_pattern_i, tokens.premsg_pattern_r)?;
+            match self.read_message(message, payload) {
+                Ok(len) => return Ok((len, index)),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn _read_message_in_place(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        let read = self.read_tokens(message)?;
+        let payload_len =
+            self.symmetricstate.decrypt_and_mix_hash_in_place(&mut message[read..])?;
+        message.copy_within(read..read + payload_len, 0);
+        self.finish_read();
+        Ok(payload_len)
+    }
+
+    /// Run `read`, advancing to the next message on success. On failure, the symmetric state
+    /// and the remote static key are rolled back, so the next message received can be read
+    /// as if the failed one had never arrived. This includes a remote static key rejected by
+    /// the observer.
+    ///
+    /// The remote ephemeral key is kept, since `into_fallback()` needs the one from the
+    /// failed message, and any later message that carries one overwrites it.
+    fn read_atomically(
+        &mut self,
+        message_len: usize,
+        read: impl FnOnce(&mut Self) -> Result<usize, Error>,
+    ) -> Result<usize, Error> {
+        let checkpoint = self.symmetricstate.checkpoint();
+        let rs = self.rs.clone();
+        let learned_rs = |hs: &Self| match hs.get_remote_static() {
+            Some(remote_static) if !rs.is_on() => hs.observer.remote_static_learned(remote_static),
+            _ => Ok(()),
+        };
+        match read(self).and_then(|res| learned_rs(self).map(|()| res)) {
             Ok(res) => {
                 self.pattern_position += 1;
                 self.my_turn = true;
+                self.observer.message_read(message_len);
                 Ok(res)
             },
             Err(err) => {
                 self.symmetricstate.restore(checkpoint);
-                Err(err)
+                self.rs = rs;
+                Err(err.at_token(self.current_token.take()))
             },
         }
     }
 
-    fn _read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
-        if message.len() > MAXMSGLEN {
+    /// Read the tokens of the current message pattern, returning the number of bytes consumed.
+    fn read_tokens(&mut self, message: &[u8]) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
             return Err(Error::Input);
         } else if self.my_turn {
             return Err(StateProblem::NotTurnToRead.into());
         } else if self.pattern_position >= self.message_patterns.len() {
             return Err(StateProblem::HandshakeAlreadyFinished.into());
         }
-        let last = self.pattern_position == (self.message_patterns.len() - 1);
-
         let dh_len = self.dh_len();
         let mut ptr = message;
         for token in &self.message_patterns[self.pattern_position] {
+            self.current_token = Some(*token);
             match *token {
                 Token::E => {
                     if ptr.len() < dh_len {
@@ -392,8 +627,8 @@ impl HandshakeState {
                     self.rs.enable();
                 },
                 Token::Psk(n) => match self.psks[n as usize] {
-                    Some(psk) => {
-                        self.symmetricstate.mix_key_and_hash(&psk);
+                    Some(ref psk) => {
+                        self.symmetricstate.mix_key_and_hash(psk);
                     },
                     None => {
                         return Err(StateProblem::MissingPsk.into());
@@ -436,30 +671,44 @@ impl HandshakeState {
                 },
             }
         }
+        self.current_token = None;
 
-        self.symmetricstate.decrypt_and_mix_hash(ptr, payload)?;
-        if last {
+        Ok(message.len() - ptr.len())
+    }
+
+    fn finish_read(&mut self) {
+        if self.pattern_position == (self.message_patterns.len() - 1) {
             self.symmetricstate.split(&mut self.cipherstates.0, &mut self.cipherstates.1);
         }
-        let payload_len = ptr.len() - if self.symmetricstate.has_key() { TAGLEN } else { 0 };
-        Ok(payload_len)
     }
 
     /// Set the preshared key at the specified location. It is up to the caller
     /// to correctly set the location based on the specified handshake - Snow
     /// won't stop you from placing a PSK in an unused slot.
     ///
+    /// This may be called between handshake messages, so a PSK can be chosen once
+    /// something has been learned about the peer (e.g. looked up by the remote static key
+    /// after reading message 1 of `IXpsk2`). It must be set before the message containing
+    /// its `psk` token is written or read, otherwise that call fails with
+    /// `StateProblem::MissingPsk`.
+    ///
     /// # Errors
     ///
     /// Will result in `Error::Input` if the PSK is not the right length or the location is out of bounds.
+    ///
+    /// Will result in `InitStage::ValidatePskStrength` if `Builder::reject_weak_psks()` was
+    /// used and the PSK is a single repeated byte.
     pub fn set_psk(&mut self, location: usize, key: &[u8]) -> Result<(), Error> {
         if key.len() != PSKLEN || self.psks.len() <= location {
             return Err(Error::Input);
+        } else if self.reject_weak_psks && crate::utils::is_weak_psk(key) {
+            return Err(InitStage::ValidatePskStrength.into());
         }
 
         let mut new_psk = [0u8; PSKLEN];
         new_psk.copy_from_slice(key);
         self.psks[location] = Some(new_psk);
+        new_psk.zeroize();
 
         Ok(())
     }
@@ -475,6 +724,15 @@ impl HandshakeState {
         self.rs.get().map(|rs| &rs[..self.dh_len()])
     }
 
+    /// Get the local ephemeral public key, once the message carrying it has been written.
+    ///
+    /// Useful to tell handshakes apart before they finish, e.g. to index retransmission state
+    /// or to derive cookies as WireGuard does.
+    #[must_use]
+    pub fn get_local_ephemeral_public(&self) -> Option<&[u8]> {
+        self.e.get().map(|e| e.pubkey())
+    }
+
     /// Get the handshake hash.
     ///
     /// Returns a slice of length `Hasher.hash_len()` (i.e. HASHLEN for the chosen Hash function).
@@ -483,6 +741,15 @@ impl HandshakeState {
         self.symmetricstate.handshake_hash()
     }
 
+    /// Copy the handshake hash out, for the transport states to keep after the handshake
+    /// state is consumed.
+    pub(crate) fn handshake_hash_buf(&self) -> (Zeroizing<[u8; MAXHASHLEN]>, usize) {
+        let handshake_hash = self.get_handshake_hash();
+        let mut h = Zeroizing::new([0u8; MAXHASHLEN]);
+        h[..handshake_hash.len()].copy_from_slice(handshake_hash);
+        (h, handshake_hash.len())
+    }
+
     /// Check if this session was started with the "initiator" role.
     #[must_use]
     pub fn is_initiator(&self) -> bool {
@@ -501,6 +768,94 @@ impl HandshakeState {
         self.my_turn
     }
 
+    /// The index of the handshake message that will be written or read next, starting at 0.
+    ///
+    /// Once the handshake is finished this is equal to [`message_count()`](Self::message_count).
+    #[must_use]
+    pub fn message_index(&self) -> usize {
+        self.pattern_position
+    }
+
+    /// The total number of messages in this handshake's pattern (including any modifiers).
+    #[must_use]
+    pub fn message_count(&self) -> usize {
+        self.message_patterns.len()
+    }
+
+    /// The tokens of the handshake message that will be written or read next, e.g.
+    /// `[E, Dh(Ee), S, Dh(Es)]` for the second message of `XX`, in the order they are
+    /// processed.
+    ///
+    /// Comparing these with a foreign implementation's view of the message helps find
+    /// mismatched pattern tables, together with the token named in a failed read's
+    /// `Error::Decrypt` or `Error::Dh`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::HandshakeAlreadyFinished` if there are no more messages.
+    pub fn next_message_pattern(&self) -> Result<&[Token], Error> {
+        self.message_patterns
+            .get(self.pattern_position)
+            .map(|tokens| &tokens[..])
+            .ok_or_else(|| StateProblem::HandshakeAlreadyFinished.into())
+    }
+
+    /// The number of bytes the next handshake message will add on top of its payload, i.e. the
+    /// size of its ephemeral and (encrypted) static keys and of any authentication tags.
+    ///
+    /// This applies to the next message whether it's ours to write or the peer's to read, so a
+    /// message buffer of `payload.len() + get_next_message_overhead()?` bytes is always enough.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::HandshakeAlreadyFinished` if there are no more messages.
+    pub fn get_next_message_overhead(&self) -> Result<usize, Error> {
+        let tokens = self
+            .message_patterns
+            .get(self.pattern_position)
+            .ok_or(StateProblem::HandshakeAlreadyFinished)?;
+        let tag_len = |has_key: bool| if has_key { TAGLEN } else { 0 };
+
+        let mut has_key = self.symmetricstate.has_key();
+        let mut overhead = 0;
+        for token in tokens {
+            match *token {
+                Token::E => {
+                    overhead += self.e.pub_len();
+                    has_key |= self.params.handshake.is_psk();
+                },
+                Token::S => overhead += self.e.pub_len() + tag_len(has_key),
+                Token::Psk(_) | Token::Dh(_) => has_key = true,
+                #[cfg(feature = "hfs")]
+                Token::E1 => {
+                    let kem = self.kem.as_ref().ok_or(Error::Input)?;
+                    overhead += kem.pub_len() + tag_len(has_key);
+                },
+                #[cfg(feature = "hfs")]
+                Token::Ekem1 => {
+                    let kem = self.kem.as_ref().ok_or(Error::Input)?;
+                    overhead += kem.ciphertext_len() + tag_len(has_key);
+                    has_key = true;
+                },
+            }
+        }
+        Ok(overhead + tag_len(has_key))
+    }
+
+    /// The security properties of the payload of the next handshake message, whether it's ours
+    /// to write or the peer's to read, e.g. to decide whether it's safe to send early data.
+    ///
+    /// See [Payload Security Properties](https://noiseprotocol.org/noise.html#payload-security-properties).
+    /// PSKs aren't taken into account, as in the spec's tables.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::HandshakeAlreadyFinished` if there are no more messages.
+    pub fn next_payload_security(&self) -> Result<(AuthLevel, ConfLevel), Error> {
+        payload_security(&self.message_patterns, self.pattern_position)
+            .ok_or_else(|| StateProblem::HandshakeAlreadyFinished.into())
+    }
+
     /// Perform the split calculation and return the resulting keys.
     ///
     /// This returns raw key material so it should be used with care. The "risky-raw-split"
@@ -512,6 +867,106 @@ impl HandshakeState {
         (output.0[..CIPHERKEYLEN].try_into().unwrap(), output.1[..CIPHERKEYLEN].try_into().unwrap())
     }
 
+    /// Restart this handshake with `params`, which must use the `fallback` modifier (e.g.
+    /// `XXfallback`), as in the Noise Pipes protocol.
+    ///
+    /// The roles are swapped, as in noise-c's `noise_handshakestate_fallback_to()`: the
+    /// original responder sends the first message of the fallback handshake, so it becomes the
+    /// initiator (`is_initiator()` returns `true`, and it sends with the first `Split()`
+    /// cipher in transport mode), while the original initiator becomes the responder.
+    ///
+    /// The original initiator's ephemeral key, and the original responder's copy of it
+    /// (received in the abandoned first message), become the pre-message. The static key and
+    /// primitives are kept; the remote static key is only carried over if the new pre-message
+    /// contains it. PSKs and the rekey policy are carried over as well.
+    ///
+    /// # Errors
+    ///
+    /// * `InitError(InitStage::ValidateFallback)` if `params` doesn't use the `fallback`
+    ///   modifier, or names different primitives than this handshake.
+    /// * `StateProblem::HandshakeAlreadyFinished` if this handshake has already completed.
+    /// * `StateProblem::MissingKeyMaterial` if the ephemeral key for the pre-message was never
+    ///   sent (initiator) or received (responder).
+    ///
+    /// See: <https://noiseprotocol.org/noise.html#noise-pipes>
+    pub fn into_fallback(
+        self,
+        params: NoiseParams,
+        prologue: &[u8],
+    ) -> Result<HandshakeState, Error> {
+        if self.is_handshake_finished() {
+            return Err(StateProblem::HandshakeAlreadyFinished.into());
+        }
+        #[cfg(feature = "hfs")]
+        let same_kem = params.kem == self.params.kem;
+        #[cfg(not(feature = "hfs"))]
+        let same_kem = true;
+        if !params.handshake.is_fallback()
+            || params.dh != self.params.dh
+            || params.cipher != self.params.cipher
+            || params.hash != self.params.hash
+            || !same_kem
+        {
+            return Err(InitStage::ValidateFallback.into());
+        }
+
+        let HandshakeState {
+            rng,
+            symmetricstate,
+            cipherstates,
+            s,
+            e,
+            fixed_ephemeral,
+            mut rs,
+            re,
+            initiator,
+            psks,
+            #[cfg(feature = "hfs")]
+            kem,
+            rekey_policy,
+            max_message_len,
+            reject_weak_psks,
+            reject_zero_dh,
+            observer,
+            ..
+        } = self;
+        let initiator = !initiator;
+        let tokens = HandshakeTokens::try_from(&params.handshake)?;
+        let remote_premsg =
+            if initiator { tokens.premsg_pattern_r } else { tokens.premsg_pattern_i };
+        if !remote_premsg.contains(&Token::S) {
+            rs = Toggle::off(*rs);
+        }
+        let (cipherstate, hasher) = symmetricstate.into_parts();
+        let cipherstate = CipherState::new(cipherstate.into_cipher());
+
+        #[allow(unused_mut)]
+        let mut hs = HandshakeState::new(
+            rng,
+            SymmetricState::new(cipherstate, hasher),
+            s,
+            e,
+            fixed_ephemeral,
+            rs,
+            re,
+            initiator,
+            params,
+            &psks,
+            prologue,
+            cipherstates,
+        )?;
+        #[cfg(feature = "hfs")]
+        if let Some(kem) = kem {
+            hs.set_kem(kem);
+        }
+        hs.rekey_policy = rekey_policy;
+        hs.max_message_len = max_message_len;
+        hs.reject_weak_psks = reject_weak_psks;
+        hs.reject_zero_dh = reject_zero_dh;
+        hs.observer = observer;
+        Ok(hs)
+    }
+
     /// Convert this `HandshakeState` into a `TransportState` with an internally stored nonce.
     ///
     /// # Errors
@@ -526,9 +981,28 @@ impl HandshakeState {
     /// # Errors
     /// A `State(StateProblem)` variant will be returned for various issues in the building of a
     /// usable `StatelessTransportState`. See `StateProblem` for further details.
+    ///
+    /// A [`RekeyPolicy`] set on the builder is ignored, as it can't be honored without an
+    /// internal nonce.
     pub fn into_stateless_transport_mode(self) -> Result<StatelessTransportState, Error> {
         self.try_into()
     }
+
+    /// Convert this `HandshakeState` into a half-duplex `TransportState`, which uses a single
+    /// `CipherState` for both directions as described in Section 11.5 of the Noise
+    /// Specification. Both peers must make the same choice.
+    ///
+    /// Only suitable for strictly alternating request-response protocols: the initiator sends
+    /// first, and after that each side must read a message before it can write the next one.
+    /// Writing out of turn fails with `StateProblem::NotTurnToWrite`, and reading out of turn
+    /// with `StateProblem::NotTurnToRead`.
+    ///
+    /// # Errors
+    /// A `State(StateProblem)` variant will be returned for various issues in the building of a
+    /// usable `TransportState`, including `StateProblem::OneWay` for one-way patterns.
+    pub fn into_half_duplex_transport_mode(self) -> Result<TransportState, Error> {
+        TransportState::new_half_duplex(self)
+    }
 }
 
 impl fmt::Debug for HandshakeState {
@@ -13,7 +13,7 @@
 //! ```
 //! # use snow::Error;
 //! #
-//! # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+//! # #[cfg(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated")))]
 //! # fn try_main() -> Result<(), Error> {
 //! static PATTERN: &'static str = "Noise_NN_25519_ChaChaPoly_BLAKE2s";
 //!
@@ -43,7 +43,7 @@
 //! #     Ok(())
 //! # }
 //! #
-//! # #[cfg(not(any(feature = "default-resolver", feature = "ring-accelerated")))]
+//! # #[cfg(not(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated"))))]
 //! # fn try_main() -> Result<(), ()> { Ok(()) }
 //! #
 //! # fn main() {
@@ -52,9 +52,26 @@
 //! ```
 //!
 //! See `examples/simple.rs` for a more complete TCP client/server example with static keys.
+//!
+//! # `no_std`
+//!
+//! With the default `std` feature disabled, the crate builds as `#![no_std]` on top of
+//! `alloc`. The default resolver then has no source of randomness, so an RNG must be supplied
+//! through a custom [`CryptoResolver`](resolvers::CryptoResolver) (see
+//! [`FallbackResolver`](resolvers::FallbackResolver) for composing one with the default).
+//!
+//! # Threads
+//!
+//! The primitive traits in [`types`] all require `Send + Sync`, so every session state
+//! ([`HandshakeState`], [`TransportState`] and its halves, [`StatelessTransportState`]) is
+//! `Send + Sync` and can be moved into another thread or task, or shared behind an `Arc`.
+//! A [`Builder`] is `Send`.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
 macro_rules! copy_slices {
     ($inslice:expr, $outslice:expr) => {
         $outslice[..$inslice.len()].copy_from_slice(&$inslice[..])
@@ -73,19 +90,35 @@ mod cipherstate;
 mod constants;
 pub mod error;
 mod handshakestate;
+mod observer;
+mod session;
 mod stateless_transportstate;
 mod symmetricstate;
+#[cfg(feature = "std")]
+pub mod sync_io;
 mod transportstate;
 mod utils;
 
+#[cfg(feature = "default-resolver")]
+pub mod ed25519;
+#[cfg(feature = "keys")]
+pub mod keys;
 pub mod params;
+#[cfg(feature = "default-resolver")]
+pub mod quick;
 pub mod resolvers;
 pub mod types;
+#[cfg(feature = "vectors")]
+pub mod vectors;
+pub mod wireguard;
 
 pub use crate::{
-    builder::{Builder, Keypair},
+    builder::{Builder, Keypair, PublicKey},
+    cipherstate::CipherState,
     error::Error,
     handshakestate::HandshakeState,
-    stateless_transportstate::StatelessTransportState,
-    transportstate::TransportState,
+    observer::SessionObserver,
+    session::Session,
+    stateless_transportstate::{ReplayWindow, StatelessTransportState},
+    transportstate::{RecvHalf, RekeyPolicy, SendHalf, TransportState, TransportStats},
 };
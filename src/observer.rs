@@ -0,0 +1,124 @@
+use crate::error::Error;
+use alloc::sync::Arc;
+
+/// Callbacks into the lifecycle of a session, e.g. for metrics, audit logging, or checking the
+/// remote party against a policy, without wrapping every call site.
+///
+/// Set one with [`Builder::observer()`](crate::Builder::observer). It is carried over from the
+/// `HandshakeState` into the transport state (and into both halves of
+/// [`TransportState::split()`](crate::TransportState::split)), so one observer sees the whole
+/// session. Every method has an empty default, so only the interesting ones need implementing.
+///
+/// The callbacks are only made for operations that succeed, never for failed ones.
+/// Lengths are those of the Noise message, i.e. ciphertext including tags, as in
+/// [`TransportStats`](crate::TransportStats). The payload is never passed to the observer.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{Builder, SessionObserver};
+/// # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+/// #
+/// #[derive(Default)]
+/// struct BytesOut(AtomicUsize);
+///
+/// impl SessionObserver for BytesOut {
+///     fn on_message_written(&self, len: usize) {
+///         self.0.fetch_add(len, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+/// # fn try_main() -> Result<(), snow::Error> {
+/// let bytes_out = Arc::new(BytesOut::default());
+/// let mut initiator = Builder::new("Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?)
+///     .observer(bytes_out.clone())?
+///     .build_initiator()?;
+/// let len = initiator.write_message(&[], &mut [0u8; 1024])?;
+/// assert_eq!(bytes_out.0.load(Ordering::Relaxed), len);
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(any(feature = "default-resolver", feature = "ring-accelerated")))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+pub trait SessionObserver: Send + Sync {
+    /// A handshake or transport message of `len` bytes was written.
+    fn on_message_written(&self, len: usize) {
+        let _ = len;
+    }
+
+    /// A handshake or transport message of `len` bytes was read successfully.
+    fn on_message_read(&self, len: usize) {
+        let _ = len;
+    }
+
+    /// The remote party's static key was received in a handshake message, which has been
+    /// read successfully up to here. Keys known before the handshake (e.g. in `KK`) aren't
+    /// reported.
+    ///
+    /// Returning an error rejects the key: the read fails with that error and, like any
+    /// failed read, leaves the handshake as it was before the message.
+    ///
+    /// # Errors
+    ///
+    /// Whichever error should fail the read.
+    fn on_remote_static_learned(&self, remote_static: &[u8]) -> Result<(), Error> {
+        let _ = remote_static;
+        Ok(())
+    }
+
+    /// The handshake with hash `handshake_hash` has finished and the session was moved into
+    /// transport mode.
+    fn on_transport_ready(&self, handshake_hash: &[u8]) {
+        let _ = handshake_hash;
+    }
+
+    /// A transport cipher is being rekeyed, in either direction, automatically or manually.
+    fn on_rekey(&self) {}
+}
+
+/// The optional observer of a session, which makes each callback a no-op if there is none.
+#[derive(Clone, Default)]
+pub(crate) struct Observer(Option<Arc<dyn SessionObserver>>);
+
+impl Observer {
+    pub(crate) fn new(observer: Option<Arc<dyn SessionObserver>>) -> Self {
+        Self(observer)
+    }
+
+    pub(crate) fn message_written(&self, len: usize) {
+        if let Some(observer) = &self.0 {
+            observer.on_message_written(len);
+        }
+    }
+
+    pub(crate) fn message_read(&self, len: usize) {
+        if let Some(observer) = &self.0 {
+            observer.on_message_read(len);
+        }
+    }
+
+    pub(crate) fn remote_static_learned(&self, remote_static: &[u8]) -> Result<(), Error> {
+        match &self.0 {
+            Some(observer) => observer.on_remote_static_learned(remote_static),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn transport_ready(&self, handshake_hash: &[u8]) {
+        if let Some(observer) = &self.0 {
+            observer.on_transport_ready(handshake_hash);
+        }
+    }
+
+    pub(crate) fn rekey(&self) {
+        if let Some(observer) = &self.0 {
+            observer.on_rekey();
+        }
+    }
+}
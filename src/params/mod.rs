@@ -1,19 +1,43 @@
-#![allow(clippy::match_on_vec_items)]
 #![allow(clippy::enum_glob_use)]
 
 //! All structures related to Noise parameter definitions (cryptographic primitive choices, protocol
 //! patterns/names)
 
 use crate::error::{Error, PatternProblem};
-use std::str::FromStr;
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+};
+use core::{fmt, str::FromStr};
+mod negotiation;
 mod patterns;
-
-pub use self::patterns::{
-    HandshakeChoice, HandshakeModifier, HandshakeModifierList, HandshakePattern,
-    SUPPORTED_HANDSHAKE_PATTERNS,
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use self::{
+    negotiation::SupportedProtocols,
+    patterns::{
+        AuthLevel, ConfLevel, DhToken, HandshakeChoice, HandshakeModifier, HandshakeModifierList,
+        HandshakePattern, Token, SUPPORTED_HANDSHAKE_PATTERNS,
+    },
 };
 
-pub(crate) use self::patterns::{DhToken, HandshakeTokens, MessagePatterns, Token};
+pub(crate) use self::patterns::{payload_security, HandshakeTokens, MessagePatterns};
+
+/// Display a choice as its name in a protocol name, i.e. `as_str()`.
+macro_rules! display_as_str {
+    ($($name:ty),* $(,)?) => {$(
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    )*};
+}
+
+display_as_str!(BaseChoice, HandshakePattern, DHChoice, CipherChoice, HashChoice);
+#[cfg(feature = "hfs")]
+display_as_str!(KemChoice);
 
 /// I recommend you choose `Noise`.
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -22,6 +46,16 @@ pub enum BaseChoice {
     Noise,
 }
 
+impl BaseChoice {
+    /// The name of this choice in a protocol name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BaseChoice::Noise => "Noise",
+        }
+    }
+}
+
 impl FromStr for BaseChoice {
     type Err = Error;
 
@@ -43,6 +77,17 @@ pub enum DHChoice {
     Curve448,
 }
 
+impl DHChoice {
+    /// The name of this choice in a protocol name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DHChoice::Curve25519 => "25519",
+            DHChoice::Curve448 => "448",
+        }
+    }
+}
+
 impl FromStr for DHChoice {
     type Err = Error;
 
@@ -56,7 +101,8 @@ impl FromStr for DHChoice {
     }
 }
 
-/// One of `ChaChaPoly` or `AESGCM`, per the spec.
+/// One of `ChaChaPoly` or `AESGCM`, per the spec, or one of the non-standard ciphers enabled by
+/// a feature flag.
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum CipherChoice {
     /// The ChaCha20Poly1305 AEAD.
@@ -68,6 +114,34 @@ pub enum CipherChoice {
     XChaChaPoly,
     /// The AES-GCM AEAD.
     AESGCM,
+    #[cfg(feature = "aesgcmsiv")]
+    /// The AES-GCM-SIV AEAD, a nonce-misuse-resistant variant of AES-GCM.
+    /// This variant is hidden behind a feature flag to highlight that it is not in the
+    /// official specification of the Noise Protocol.
+    AESGCMSIV,
+    #[cfg(feature = "aegis")]
+    /// The AEGIS-256 AEAD, which is much faster than AES-GCM on CPUs with AES instructions.
+    /// This variant is hidden behind a feature flag to highlight that it is not in the
+    /// official specification of the Noise Protocol.
+    AEGIS256,
+}
+
+impl CipherChoice {
+    /// The name of this choice in a protocol name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        use self::CipherChoice::*;
+        match self {
+            ChaChaPoly => "ChaChaPoly",
+            #[cfg(feature = "xchachapoly")]
+            XChaChaPoly => "XChaChaPoly",
+            AESGCM => "AESGCM",
+            #[cfg(feature = "aesgcmsiv")]
+            AESGCMSIV => "AESGCMSIV",
+            #[cfg(feature = "aegis")]
+            AEGIS256 => "AEGIS256",
+        }
+    }
 }
 
 impl FromStr for CipherChoice {
@@ -80,6 +154,10 @@ impl FromStr for CipherChoice {
             #[cfg(feature = "xchachapoly")]
             "XChaChaPoly" => Ok(XChaChaPoly),
             "AESGCM" => Ok(AESGCM),
+            #[cfg(feature = "aesgcmsiv")]
+            "AESGCMSIV" => Ok(AESGCMSIV),
+            #[cfg(feature = "aegis")]
+            "AEGIS256" => Ok(AEGIS256),
             _ => Err(PatternProblem::UnsupportedCipherType.into()),
         }
     }
@@ -97,6 +175,41 @@ pub enum HashChoice {
     Blake2s,
     /// The BLAKE2b hash function, designed to be more efficient on 64-bit architectures.
     Blake2b,
+    #[cfg(feature = "sha3")]
+    /// The SHA3-256 hash function.
+    /// This variant is hidden behind a feature flag to highlight that it is not in the
+    /// official specification of the Noise Protocol.
+    SHA3_256,
+    #[cfg(feature = "sha3")]
+    /// The SHA3-512 hash function.
+    /// This variant is hidden behind a feature flag to highlight that it is not in the
+    /// official specification of the Noise Protocol.
+    SHA3_512,
+    #[cfg(feature = "blake3")]
+    /// The BLAKE3 hash function.
+    /// This variant is hidden behind a feature flag to highlight that it is not in the
+    /// official specification of the Noise Protocol.
+    Blake3,
+}
+
+impl HashChoice {
+    /// The name of this choice in a protocol name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        use self::HashChoice::*;
+        match self {
+            SHA256 => "SHA256",
+            SHA512 => "SHA512",
+            Blake2s => "BLAKE2s",
+            Blake2b => "BLAKE2b",
+            #[cfg(feature = "sha3")]
+            SHA3_256 => "SHA3/256",
+            #[cfg(feature = "sha3")]
+            SHA3_512 => "SHA3/512",
+            #[cfg(feature = "blake3")]
+            Blake3 => "BLAKE3",
+        }
+    }
 }
 
 impl FromStr for HashChoice {
@@ -109,6 +222,12 @@ impl FromStr for HashChoice {
             "SHA512" => Ok(SHA512),
             "BLAKE2s" => Ok(Blake2s),
             "BLAKE2b" => Ok(Blake2b),
+            #[cfg(feature = "sha3")]
+            "SHA3/256" => Ok(SHA3_256),
+            #[cfg(feature = "sha3")]
+            "SHA3/512" => Ok(SHA3_512),
+            #[cfg(feature = "blake3")]
+            "BLAKE3" => Ok(Blake3),
             _ => Err(PatternProblem::UnsupportedHashType.into()),
         }
     }
@@ -122,6 +241,17 @@ pub enum KemChoice {
     Kyber1024,
 }
 
+#[cfg(feature = "hfs")]
+impl KemChoice {
+    /// The name of this choice in a protocol name.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KemChoice::Kyber1024 => "Kyber1024",
+        }
+    }
+}
+
 #[cfg(feature = "hfs")]
 impl FromStr for KemChoice {
     type Err = Error;
@@ -148,6 +278,27 @@ impl FromStr for KemChoice {
 ///
 /// let params: NoiseParams = "Noise_XX_25519_AESGCM_SHA256".parse().unwrap();
 /// ```
+///
+/// From the individual choices:
+///
+/// ```
+/// # use snow::params::*;
+///
+/// let handshake = HandshakeChoice {
+///     pattern:   HandshakePattern::XX,
+///     modifiers: HandshakeModifierList { list: vec![HandshakeModifier::Psk(3)] },
+/// };
+/// # #[cfg(not(feature = "hfs"))]
+/// let params = NoiseParams::from_choices(
+///     BaseChoice::Noise,
+///     handshake,
+///     DHChoice::Curve25519,
+///     CipherChoice::AESGCM,
+///     HashChoice::SHA256,
+/// );
+/// # #[cfg(not(feature = "hfs"))]
+/// assert_eq!(params, "Noise_XXpsk3_25519_AESGCM_SHA256".parse().unwrap());
+/// ```
 #[derive(PartialEq, Clone, Debug)]
 #[allow(clippy::module_name_repetitions)]
 pub struct NoiseParams {
@@ -196,6 +347,50 @@ impl NoiseParams {
     ) -> Self {
         NoiseParams { name, base, handshake, dh, kem, cipher, hash }
     }
+
+    #[cfg(not(feature = "hfs"))]
+    /// Construct a new `NoiseParams` from its choices, deriving the protocol name from them.
+    #[must_use]
+    pub fn from_choices(
+        base: BaseChoice,
+        handshake: HandshakeChoice,
+        dh: DHChoice,
+        cipher: CipherChoice,
+        hash: HashChoice,
+    ) -> Self {
+        let mut params = NoiseParams { name: String::new(), base, handshake, dh, cipher, hash };
+        params.name = params.to_string();
+        params
+    }
+
+    #[cfg(feature = "hfs")]
+    /// Construct a new `NoiseParams` from its choices, deriving the protocol name from them.
+    pub fn from_choices(
+        base: BaseChoice,
+        handshake: HandshakeChoice,
+        dh: DHChoice,
+        kem: Option<KemChoice>,
+        cipher: CipherChoice,
+        hash: HashChoice,
+    ) -> Self {
+        let mut params =
+            NoiseParams { name: String::new(), base, handshake, dh, kem, cipher, hash };
+        params.name = params.to_string();
+        params
+    }
+}
+
+/// Formats the canonical protocol name of the choices, which is the string they were parsed
+/// from (as `name` holds), unless the fields were changed since.
+impl fmt::Display for NoiseParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}_{}", self.base, self.handshake, self.dh)?;
+        #[cfg(feature = "hfs")]
+        if let Some(kem) = self.kem {
+            write!(f, "+{kem}")?;
+        }
+        write!(f, "_{}_{}", self.cipher, self.hash)
+    }
 }
 
 impl FromStr for NoiseParams {
@@ -270,18 +465,128 @@ mod tests {
         assert!(p.handshake.modifiers.list.is_empty());
     }
 
+    #[test]
+    #[cfg(not(feature = "hfs"))]
+    fn test_from_choices_names() {
+        for name in [
+            "Noise_XX_25519_AESGCM_SHA256",
+            "Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s",
+            "Noise_XXfallback+psk0_448_ChaChaPoly_BLAKE2b",
+            "Noise_NNpsk0+psk2_25519_AESGCM_SHA512",
+        ] {
+            let p: NoiseParams = name.parse().unwrap();
+            let built =
+                NoiseParams::from_choices(p.base, p.handshake.clone(), p.dh, p.cipher, p.hash);
+            assert_eq!(built, p);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let mut names: Vec<String> = SUPPORTED_HANDSHAKE_PATTERNS
+            .iter()
+            .map(|pattern| format!("Noise_{pattern}_25519_ChaChaPoly_BLAKE2s"))
+            .collect();
+        names.extend(
+            [
+                "Noise_XXfallback+psk3_25519_AESGCM_SHA256",
+                "Noise_XXpsk3+fallback_25519_AESGCM_SHA256",
+                "Noise_NNpsk0+psk2_448_ChaChaPoly_SHA512",
+                "Noise_IKpsk2_25519_ChaChaPoly_BLAKE2b",
+            ]
+            .map(String::from),
+        );
+        #[cfg(feature = "hfs")]
+        names.push("Noise_XXhfs+psk3_25519+Kyber1024_ChaChaPoly_BLAKE2s".into());
+
+        for name in names {
+            let p: NoiseParams = name.parse().unwrap();
+            assert_eq!(p.to_string(), name);
+            assert_eq!(p.to_string().parse::<NoiseParams>().unwrap(), p);
+        }
+
+        // Display follows the fields, so it stays accurate when they're changed.
+        let mut p: NoiseParams = "Noise_XX_25519_AESGCM_SHA256".parse().unwrap();
+        p.handshake.modifiers.list.push(HandshakeModifier::Fallback);
+        assert_eq!(p.to_string(), "Noise_XXfallback_25519_AESGCM_SHA256");
+    }
+
     #[test]
     fn test_basic_deferred() {
         let p: NoiseParams = "Noise_X1X1_25519_AESGCM_SHA256".parse().unwrap();
         assert!(p.handshake.modifiers.list.is_empty());
     }
 
+    #[test]
+    fn test_static_key_requirements_match_patterns() {
+        for &pattern in SUPPORTED_HANDSHAKE_PATTERNS {
+            let choice = HandshakeChoice { pattern, modifiers: "".parse().unwrap() };
+            let tokens = HandshakeTokens::try_from(&choice).unwrap();
+            for initiator in [true, false] {
+                let (own_premsg, remote_premsg) = if initiator {
+                    (tokens.premsg_pattern_i, tokens.premsg_pattern_r)
+                } else {
+                    (tokens.premsg_pattern_r, tokens.premsg_pattern_i)
+                };
+                let sends_s = tokens
+                    .msg_patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| (i % 2 == 0) == initiator)
+                    .any(|(_, msg)| msg.contains(&Token::S));
+
+                assert_eq!(
+                    pattern.needs_local_static_key(initiator),
+                    own_premsg.contains(&Token::S) || sends_s,
+                    "{pattern:?} (initiator: {initiator})"
+                );
+                assert_eq!(
+                    pattern.need_known_remote_pubkey(initiator),
+                    remote_premsg.contains(&Token::S),
+                    "{pattern:?} (initiator: {initiator})"
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_fallback_mod() {
         let p: NoiseParams = "Noise_XXfallback_25519_AESGCM_SHA256".parse().unwrap();
         assert!(p.handshake.modifiers.list[0] == HandshakeModifier::Fallback);
     }
 
+    #[test]
+    fn test_fallback_handshake() {
+        let p: NoiseParams = "Noise_XXfallback_25519_AESGCM_SHA256".parse().unwrap();
+        let tokens = HandshakeTokens::try_from(&p.handshake).unwrap();
+        assert!(tokens.premsg_pattern_i.is_empty());
+        assert_eq!(tokens.premsg_pattern_r, &[Token::E]);
+        assert_eq!(tokens.msg_patterns.len(), 2);
+        assert_eq!(
+            tokens.msg_patterns[0][..],
+            [Token::E, Token::Dh(DhToken::Ee), Token::S, Token::Dh(DhToken::Se)]
+        );
+        assert_eq!(tokens.msg_patterns[1][..], [Token::S, Token::Dh(DhToken::Es)]);
+    }
+
+    #[test]
+    fn test_invalid_fallback_handshake() {
+        for name in [
+            "Noise_IKfallback_25519_AESGCM_SHA256",
+            "Noise_KNfallback_25519_AESGCM_SHA256",
+            "Noise_NK1fallback_25519_AESGCM_SHA256",
+            "Noise_Nfallback_25519_AESGCM_SHA256",
+            "Noise_XXpsk0+fallback_25519_AESGCM_SHA256",
+        ] {
+            let p: NoiseParams = name.parse().unwrap();
+            assert_eq!(
+                Error::Pattern(PatternProblem::UnsupportedModifier),
+                HandshakeTokens::try_from(&p.handshake).unwrap_err(),
+                "{name}"
+            );
+        }
+    }
+
     #[test]
     fn test_psk_fallback_mod() {
         let p: NoiseParams = "Noise_XXfallback+psk0_25519_AESGCM_SHA256".parse().unwrap();
@@ -367,4 +672,97 @@ mod tests {
             "Noise_XXpsk0_25519_AESGCM_SHA256_HackThePlanet".parse::<NoiseParams>().unwrap_err()
         );
     }
+
+    #[test]
+    fn test_payload_security_matches_spec_tables() {
+        let table: &[(&str, &[(u8, u8)])] = &[
+            ("N", &[(0, 2)]),
+            ("K", &[(1, 2)]),
+            ("X", &[(1, 2)]),
+            ("NN", &[(0, 0), (0, 1)]),
+            ("NK", &[(0, 2), (2, 1)]),
+            ("NX", &[(0, 0), (2, 1)]),
+            ("XN", &[(0, 0), (0, 1), (2, 1)]),
+            ("XK", &[(0, 2), (2, 1), (2, 5)]),
+            ("XX", &[(0, 0), (2, 1), (2, 5)]),
+            ("KN", &[(0, 0), (0, 3)]),
+            ("KK", &[(1, 2), (2, 4)]),
+            ("KX", &[(0, 0), (2, 3)]),
+            ("IN", &[(0, 0), (0, 3)]),
+            ("IK", &[(1, 2), (2, 4)]),
+            ("IX", &[(0, 0), (2, 3)]),
+        ];
+        for (pattern, expected) in table {
+            let choice: HandshakeChoice = pattern.parse().unwrap();
+            let tokens = HandshakeTokens::try_from(&choice).unwrap();
+            let actual: Vec<_> = (0..tokens.msg_patterns.len())
+                .map(|i| payload_security(&tokens.msg_patterns, i).unwrap())
+                .map(|(auth, conf)| (auth as u8, conf as u8))
+                .collect();
+            assert_eq!(&actual[..], *expected, "{pattern}");
+            assert_eq!(payload_security(&tokens.msg_patterns, expected.len()), None);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_names() {
+        #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+        struct Config {
+            params:    NoiseParams,
+            cipher:    CipherChoice,
+            modifiers: HandshakeModifierList,
+        }
+
+        let json = r#"{"params":"Noise_XXfallback+psk3_25519_AESGCM_SHA256","cipher":"ChaChaPoly","modifiers":"fallback+psk3"}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(config.params, "Noise_XXfallback+psk3_25519_AESGCM_SHA256".parse().unwrap());
+        assert_eq!(config.cipher, CipherChoice::ChaChaPoly);
+        assert_eq!(config.modifiers, config.params.handshake.modifiers);
+        assert_eq!(serde_json::to_string(&config).unwrap(), json);
+
+        assert!(
+            serde_json::from_str::<NoiseParams>(r#""Noise_XX_25519_ChaChaPoly_BLAH256""#).is_err()
+        );
+        assert!(serde_json::from_str::<DHChoice>(r#""P256""#).is_err());
+    }
+
+    #[test]
+    fn test_supported_protocols() {
+        let protocols = |names: &[&str]| {
+            SupportedProtocols::new(names.iter().map(|name| name.parse().unwrap()).collect())
+                .unwrap()
+        };
+        let a = protocols(&[
+            "Noise_XX_25519_AESGCM_SHA256",
+            "Noise_XX_25519_ChaChaPoly_BLAKE2s",
+            "Noise_XX_25519_ChaChaPoly_SHA256",
+        ]);
+        let b =
+            protocols(&["Noise_XX_25519_ChaChaPoly_SHA256", "Noise_XX_25519_ChaChaPoly_BLAKE2s"]);
+
+        // The initiator's preference decides, whichever side that is.
+        assert_eq!(a.select(&b.offer(), true).unwrap().name, "Noise_XX_25519_ChaChaPoly_BLAKE2s");
+        assert_eq!(b.select(&a.offer(), false).unwrap().name, "Noise_XX_25519_ChaChaPoly_BLAKE2s");
+        assert_eq!(b.select(&a.offer(), true).unwrap().name, "Noise_XX_25519_ChaChaPoly_SHA256");
+        assert_eq!(a.select(&b.offer(), false).unwrap().name, "Noise_XX_25519_ChaChaPoly_SHA256");
+
+        // Unknown names are skipped, and no overlap is an error.
+        let mut offer = vec![2, 3];
+        offer.extend_from_slice(b"???");
+        offer.push(32);
+        offer.extend_from_slice(b"Noise_XX_25519_ChaChaPoly_SHA256");
+        assert_eq!(a.select(&offer, false).unwrap().name, "Noise_XX_25519_ChaChaPoly_SHA256");
+        let c = protocols(&["Noise_NN_25519_AESGCM_SHA256"]);
+        assert_eq!(a.select(&c.offer(), true), Err(Error::Negotiation));
+
+        // Malformed offers and lists.
+        let offer = a.offer();
+        assert_eq!(a.select(&offer[..offer.len() - 1], true), Err(Error::Input));
+        assert_eq!(a.select(&[offer.as_slice(), &[0]].concat(), true), Err(Error::Input));
+        assert_eq!(a.select(&[], true), Err(Error::Input));
+        assert_eq!(SupportedProtocols::new(vec![]), Err(Error::Input));
+        let xx: NoiseParams = "Noise_XX_25519_AESGCM_SHA256".parse().unwrap();
+        assert_eq!(SupportedProtocols::new(vec![xx.clone(), xx]), Err(Error::Input));
+    }
 }
@@ -0,0 +1,132 @@
+use super::NoiseParams;
+use crate::error::Error;
+use alloc::vec::Vec;
+
+/// An ordered list of the protocols one side supports, most preferred first, for agreeing on
+/// the protocol of a handshake with a peer.
+///
+/// Each side sends its [`offer()`](Self::offer) to the other, and both call
+/// [`select()`](Self::select) with the peer's offer. The selection is the initiator's most
+/// preferred protocol that the responder also supports, so both sides arrive at the same one
+/// without another round trip.
+///
+/// The offers themselves aren't authenticated, so an attacker could strip the strongest
+/// protocols from one of them to force a weaker choice. To detect this, both sides should
+/// add both offers to the prologue of the handshake, initiator's first (e.g. with
+/// [`Builder::append_prologue()`](crate::Builder::append_prologue)): a tampered offer then
+/// makes the prologues differ, and the handshake fails.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{params::SupportedProtocols, Builder};
+/// #
+/// # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+/// # fn try_main() -> Result<(), snow::Error> {
+/// let client = SupportedProtocols::new(vec![
+///     "Noise_NN_25519_AESGCM_SHA256".parse()?,
+///     "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?,
+/// ])?;
+/// let server = SupportedProtocols::new(vec!["Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?])?;
+/// let (client_offer, server_offer) = (client.offer(), server.offer());
+///
+/// let params = client.select(&server_offer, true)?;
+/// assert_eq!(params, server.select(&client_offer, false)?);
+/// let initiator = Builder::new(params)
+///     .append_prologue(&client_offer)
+///     .append_prologue(&server_offer)
+///     .build_initiator()?;
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(any(feature = "default-resolver", feature = "ring-accelerated")))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupportedProtocols {
+    protocols: Vec<NoiseParams>,
+}
+
+impl SupportedProtocols {
+    /// Build the list from `protocols`, most preferred first.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `protocols` is empty, has more than 255 entries or a
+    /// name longer than 255 bytes, or names a protocol twice.
+    pub fn new(protocols: Vec<NoiseParams>) -> Result<Self, Error> {
+        let duplicate = protocols
+            .iter()
+            .enumerate()
+            .any(|(i, p)| protocols[..i].iter().any(|q| q.name == p.name));
+        let too_long = protocols.iter().any(|p| p.name.len() > usize::from(u8::MAX));
+        if protocols.is_empty() || protocols.len() > usize::from(u8::MAX) || too_long || duplicate {
+            return Err(Error::Input);
+        }
+        Ok(Self { protocols })
+    }
+
+    /// The protocols, most preferred first.
+    #[must_use]
+    pub fn protocols(&self) -> &[NoiseParams] {
+        &self.protocols
+    }
+
+    /// Encode the list as an offer for the peer: a count byte, then each protocol name
+    /// preceded by its length as one byte.
+    #[must_use]
+    pub fn offer(&self) -> Vec<u8> {
+        let mut offer = Vec::new();
+        // `new()` limits both the number of protocols and the length of their names to 255.
+        offer.push(self.protocols.len() as u8);
+        for protocol in &self.protocols {
+            offer.push(protocol.name.len() as u8);
+            offer.extend_from_slice(protocol.name.as_bytes());
+        }
+        offer
+    }
+
+    /// Select the protocol to use with a peer whose offer is `peer_offer`, where `initiator`
+    /// says whether this side is the initiator of the handshake.
+    ///
+    /// Names in the offer that aren't valid protocol names, e.g. ones only the peer's version
+    /// of snow supports, are simply never selected.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `peer_offer` isn't a well-formed offer, and in
+    /// `Error::Negotiation` if the two sides have no protocol in common.
+    pub fn select(&self, peer_offer: &[u8], initiator: bool) -> Result<NoiseParams, Error> {
+        let peer = parse_offer(peer_offer)?;
+        let supported_by_peer = |protocol: &&NoiseParams| peer.contains(&protocol.name.as_bytes());
+        let selected = if initiator {
+            self.protocols.iter().find(supported_by_peer)
+        } else {
+            peer.iter().find_map(|name| self.protocols.iter().find(|p| p.name.as_bytes() == *name))
+        };
+        selected.cloned().ok_or(Error::Negotiation)
+    }
+}
+
+/// Split an offer into the protocol names it contains.
+fn parse_offer(offer: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let (&count, mut rest) = offer.split_first().ok_or(Error::Input)?;
+    let mut names = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (&len, tail) = rest.split_first().ok_or(Error::Input)?;
+        if tail.len() < usize::from(len) {
+            return Err(Error::Input);
+        }
+        let (name, tail) = tail.split_at(usize::from(len));
+        names.push(name);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(Error::Input);
+    }
+    Ok(names)
+}
@@ -1,7 +1,8 @@
 #![allow(clippy::enum_glob_use)]
 
 use crate::error::{Error, PatternProblem};
-use std::{convert::TryFrom, str::FromStr};
+use alloc::{vec, vec::Vec};
+use core::{convert::TryFrom, fmt, str::FromStr};
 
 /// A small helper macro that behaves similar to the `vec![]` standard macro,
 /// except it allocates a bit extra to avoid resizing.
@@ -74,29 +75,69 @@ macro_rules! pattern_enum {
 /// The tokens which describe patterns involving DH calculations.
 ///
 /// See: <https://noiseprotocol.org/noise.html#handshake-patterns>
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub(crate) enum DhToken {
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DhToken {
+    /// `ee`: DH between the initiator's and the responder's ephemeral keys.
     Ee,
+    /// `es`: DH between the initiator's ephemeral key and the responder's static key.
     Es,
+    /// `se`: DH between the initiator's static key and the responder's ephemeral key.
     Se,
+    /// `ss`: DH between the initiator's and the responder's static keys.
     Ss,
 }
 
-/// The tokens which describe message patterns.
+impl fmt::Display for DhToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DhToken::Ee => "ee",
+            DhToken::Es => "es",
+            DhToken::Se => "se",
+            DhToken::Ss => "ss",
+        })
+    }
+}
+
+/// The tokens which describe message patterns, as returned by
+/// [`HandshakeState::next_message_pattern()`](crate::HandshakeState::next_message_pattern).
+///
+/// `Display` gives the token's name in the spec, e.g. `es` or `psk`.
 ///
 /// See: <https://noiseprotocol.org/noise.html#handshake-patterns>
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub(crate) enum Token {
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Token {
+    /// `e`: the sender's ephemeral public key.
     E,
+    /// `s`: the sender's static public key, encrypted once a key has been mixed in.
     S,
+    /// A DH calculation.
     Dh(DhToken),
+    /// `psk`: the PSK at the given position (as in the `pskN` modifier) is mixed in.
     Psk(u8),
+    /// `e1`: the sender's ephemeral KEM public key.
     #[cfg(feature = "hfs")]
     E1,
+    /// `ekem1`: the KEM ciphertext encapsulated to the remote ephemeral KEM key.
     #[cfg(feature = "hfs")]
     Ekem1,
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::E => f.write_str("e"),
+            Token::S => f.write_str("s"),
+            Token::Dh(token) => token.fmt(f),
+            Token::Psk(_) => f.write_str("psk"),
+            #[cfg(feature = "hfs")]
+            Token::E1 => f.write_str("e1"),
+            #[cfg(feature = "hfs")]
+            Token::Ekem1 => f.write_str("ekem1"),
+        }
+    }
+}
+
 #[cfg(feature = "hfs")]
 impl Token {
     fn is_dh(&self) -> bool {
@@ -188,6 +229,17 @@ impl FromStr for HandshakeModifier {
     }
 }
 
+impl fmt::Display for HandshakeModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeModifier::Psk(n) => write!(f, "psk{n}"),
+            HandshakeModifier::Fallback => write!(f, "fallback"),
+            #[cfg(feature = "hfs")]
+            HandshakeModifier::Hfs => write!(f, "hfs"),
+        }
+    }
+}
+
 /// Handshake modifiers that will be used during key exchange handshake.
 #[derive(Clone, PartialEq, Debug)]
 pub struct HandshakeModifierList {
@@ -216,6 +268,18 @@ impl FromStr for HandshakeModifierList {
     }
 }
 
+impl fmt::Display for HandshakeModifierList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, modifier) in self.list.iter().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{modifier}")?;
+        }
+        Ok(())
+    }
+}
+
 /// The pattern/modifier combination choice (no primitives specified)
 /// for a full noise protocol definition.
 #[derive(Clone, PartialEq, Debug)]
@@ -265,6 +329,12 @@ impl HandshakeChoice {
     }
 }
 
+impl fmt::Display for HandshakeChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.pattern, self.modifiers)
+    }
+}
+
 impl FromStr for HandshakeChoice {
     type Err = Error;
 
@@ -291,6 +361,98 @@ pub(crate) struct HandshakeTokens {
 
 use self::{DhToken::*, HandshakePattern::*, Token::*};
 
+/// The "source" (authentication) property of a handshake payload, from the
+/// [Payload Security Properties](https://noiseprotocol.org/noise.html#payload-security-properties)
+/// section of the Noise spec. Converting with `as u8` gives the spec's number.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[repr(u8)]
+pub enum AuthLevel {
+    /// 0: the payload may have been sent by any party, including an active attacker.
+    None          = 0,
+    /// 1: the sender is authenticated by a static-static DH, which is vulnerable to
+    /// key-compromise impersonation if the recipient's static private key leaks.
+    KciVulnerable = 1,
+    /// 2: the sender is authenticated by a DH of its static key with the recipient's
+    /// ephemeral key, which resists key-compromise impersonation.
+    KciResistant  = 2,
+}
+
+/// The "destination" (confidentiality) property of a handshake payload, from the
+/// [Payload Security Properties](https://noiseprotocol.org/noise.html#payload-security-properties)
+/// section of the Noise spec. Converting with `as u8` gives the spec's number.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[repr(u8)]
+pub enum ConfLevel {
+    /// 0: the payload is sent in the clear.
+    None                               = 0,
+    /// 1: encrypted to an ephemeral recipient that hasn't been authenticated, with forward
+    /// secrecy.
+    EphemeralRecipient                 = 1,
+    /// 2: encrypted to a known recipient with no ephemeral contribution from it, so the payload
+    /// can be replayed and is exposed if the recipient's static key leaks.
+    KnownRecipientReplayable           = 2,
+    /// 3: encrypted to a known recipient with weak forward secrecy, since the binding of the
+    /// recipient's ephemeral key to its static key hasn't been verified.
+    KnownRecipientWeakForwardSecrecy   = 3,
+    /// 4: like 3, but the binding has been verified with DHs involving the sender's static
+    /// key, so it only fails if that key was compromised before the handshake.
+    KnownRecipientWeakForwardSecrecyIfSenderCompromised = 4,
+    /// 5: encrypted to a known recipient with strong forward secrecy.
+    KnownRecipientStrongForwardSecrecy = 5,
+}
+
+/// Work out the security properties of the payload of message `index` from the DHs performed
+/// up to and including it. PSKs aren't taken into account, as in the spec's tables.
+pub(crate) fn payload_security(
+    messages: &MessagePatterns,
+    index: usize,
+) -> Option<(AuthLevel, ConfLevel)> {
+    if index >= messages.len() {
+        return None;
+    }
+
+    // Which DHs have been done so far, named from the point of view of the sender of `index`.
+    let sender_is_initiator = index & 1 == 0;
+    let (mut ee, mut ss, mut se, mut es) = (false, false, false, false);
+    for token in messages[..=index].iter().flatten() {
+        match (*token, sender_is_initiator) {
+            (Dh(Ee), _) => ee = true,
+            (Dh(Ss), _) => ss = true,
+            // sender static with recipient ephemeral
+            (Dh(Se), true) | (Dh(Es), false) => se = true,
+            // sender ephemeral with recipient static
+            (Dh(Es), true) | (Dh(Se), false) => es = true,
+            _ => {},
+        }
+    }
+
+    let auth = if se {
+        AuthLevel::KciResistant
+    } else if ss {
+        AuthLevel::KciVulnerable
+    } else {
+        AuthLevel::None
+    };
+    let conf = match (ee, es || ss) {
+        (false, false) => ConfLevel::None,
+        (true, false) => ConfLevel::EphemeralRecipient,
+        (false, true) => ConfLevel::KnownRecipientReplayable,
+        // The recipient's ephemeral key is only as trustworthy as the authentication of the
+        // last message it sent.
+        (true, true) if es => {
+            match index.checked_sub(1).and_then(|i| payload_security(messages, i)) {
+                Some((AuthLevel::KciResistant, _)) => ConfLevel::KnownRecipientStrongForwardSecrecy,
+                Some((AuthLevel::KciVulnerable, _)) => {
+                    ConfLevel::KnownRecipientWeakForwardSecrecyIfSenderCompromised
+                },
+                _ => ConfLevel::KnownRecipientWeakForwardSecrecy,
+            }
+        },
+        (true, true) => ConfLevel::EphemeralRecipient,
+    };
+    Some((auth, conf))
+}
+
 type Patterns = (PremessagePatterns, PremessagePatterns, MessagePatterns);
 
 impl<'a> TryFrom<&'a HandshakeChoice> for HandshakeTokens {
@@ -502,9 +664,9 @@ impl<'a> TryFrom<&'a HandshakeChoice> for HandshakeTokens {
         for modifier in &handshake.modifiers.list {
             match modifier {
                 HandshakeModifier::Psk(n) => apply_psk_modifier(&mut patterns, *n)?,
+                HandshakeModifier::Fallback => apply_fallback_modifier(&mut patterns)?,
                 #[cfg(feature = "hfs")]
                 HandshakeModifier::Hfs => apply_hfs_modifier(&mut patterns),
-                _ => return Err(PatternProblem::UnsupportedModifier.into()),
             }
         }
 
@@ -543,6 +705,38 @@ fn apply_psk_modifier(patterns: &mut Patterns, n: u8) -> Result<(), Error> {
     Ok(())
 }
 
+/// Convert Alice's first message into a pre-message, giving a Bob-initiated pattern.
+///
+/// As in noise-c, the roles are swapped: Bob sends the first remaining message, so he is the
+/// initiator (and the first `Split()` cipher is his to send with), while Alice's pre-message
+/// becomes the responder's. The `es`/`se` tokens are swapped to match, since they're
+/// interpreted from the initiator's point of view.
+///
+/// Only patterns without pre-messages whose first message consists of nothing but `e` (and
+/// optionally `s`) can be converted, since anything else would require a DH or PSK that a
+/// pre-message can't carry.
+///
+/// See: <https://noiseprotocol.org/noise.html#the-fallback-modifier>
+fn apply_fallback_modifier(patterns: &mut Patterns) -> Result<(), Error> {
+    if patterns.2.len() < 2 || !patterns.0.is_empty() || !patterns.1.is_empty() {
+        return Err(PatternProblem::UnsupportedModifier.into());
+    }
+    patterns.1 = match patterns.2[0][..] {
+        [E] => static_slice![Token: E],
+        [E, S] => static_slice![Token: E, S],
+        _ => return Err(PatternProblem::UnsupportedModifier.into()),
+    };
+    patterns.2.remove(0);
+    for token in patterns.2.iter_mut().flatten() {
+        *token = match *token {
+            Dh(Es) => Dh(Se),
+            Dh(Se) => Dh(Es),
+            token => token,
+        };
+    }
+    Ok(())
+}
+
 #[cfg(feature = "hfs")]
 fn apply_hfs_modifier(patterns: &mut Patterns) {
     // From the HFS spec, Section 5:
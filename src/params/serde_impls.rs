@@ -0,0 +1,42 @@
+//! `serde` support for the parameter types, which are (de)serialized as the names they have in
+//! a protocol name, e.g. `"Noise_XX_25519_ChaChaPoly_BLAKE2s"` or `"ChaChaPoly"`, so that
+//! deserializing validates them like parsing does.
+
+#[cfg(feature = "hfs")]
+use super::KemChoice;
+use super::{
+    BaseChoice, CipherChoice, DHChoice, HandshakeChoice, HandshakeModifier, HandshakeModifierList,
+    HandshakePattern, HashChoice, NoiseParams,
+};
+use alloc::string::String;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+macro_rules! serde_as_str {
+    ($($name:ty),* $(,)?) => {$(
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+            }
+        }
+    )*};
+}
+
+serde_as_str!(
+    NoiseParams,
+    BaseChoice,
+    HandshakePattern,
+    HandshakeModifier,
+    HandshakeModifierList,
+    HandshakeChoice,
+    DHChoice,
+    CipherChoice,
+    HashChoice,
+);
+#[cfg(feature = "hfs")]
+serde_as_str!(KemChoice);
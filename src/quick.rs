@@ -0,0 +1,91 @@
+//! One-call constructors for prototypes, with a recommended protocol and no choices to make.
+//!
+//! The protocol is [`PROTOCOL`], `Noise_IK_25519_ChaChaPoly_BLAKE2s`: the client knows the
+//! server's static public key in advance and only talks to that server, while the server learns
+//! the client's static key from the first message (see
+//! [`HandshakeState::get_remote_static()`]). The client's static key is generated on every
+//! call, so the server can't tell returning clients apart; when it needs to, or when the peers
+//! should learn each other's keys during the handshake (e.g. `XX`), use the [`Builder`]
+//! directly.
+//!
+//! # Examples
+//!
+//! ```
+//! # use snow::quick;
+//! #
+//! # #[cfg(feature = "std")]
+//! # fn try_main() -> Result<(), snow::Error> {
+//! let server_keypair = quick::keypair()?;
+//! let mut server = quick::server(&server_keypair)?;
+//! let mut client = quick::client(&server_keypair.public)?;
+//!
+//! let (mut message, mut payload) = ([0u8; 1024], [0u8; 1024]);
+//! let len = client.write_message(&[], &mut message)?;
+//! server.read_message(&message[..len], &mut payload)?;
+//! let len = server.write_message(&[], &mut message)?;
+//! client.read_message(&message[..len], &mut payload)?;
+//!
+//! let mut client = client.into_transport_mode()?;
+//! let mut server = server.into_transport_mode()?;
+//! let len = client.write_message(b"hello", &mut message)?;
+//! let len = server.read_message(&message[..len], &mut payload)?;
+//! assert_eq!(&payload[..len], b"hello");
+//! #     Ok(())
+//! # }
+//! #
+//! # #[cfg(not(feature = "std"))]
+//! # fn try_main() -> Result<(), ()> { Ok(()) }
+//! #
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use crate::{
+    builder::{Builder, Keypair},
+    error::Error,
+    handshakestate::HandshakeState,
+    params::NoiseParams,
+};
+
+/// The protocol used by every function of this module.
+pub const PROTOCOL: &str = "Noise_IK_25519_ChaChaPoly_BLAKE2s";
+
+/// The parameters of [`PROTOCOL`].
+#[must_use]
+pub fn params() -> NoiseParams {
+    PROTOCOL.parse().expect("PROTOCOL is a valid protocol name")
+}
+
+/// Generate a static keypair for a [`server()`], whose public key is then given to clients.
+///
+/// # Errors
+///
+/// Will result in the errors of [`Builder::generate_keypair()`].
+pub fn keypair() -> Result<Keypair, Error> {
+    Builder::new(params()).generate_keypair()
+}
+
+/// Build the initiator of a handshake with the server whose static public key is
+/// `server_public`, using a freshly generated static key of its own.
+///
+/// # Errors
+///
+/// Will result in the errors of [`Builder::generate_keypair()`] and
+/// [`Builder::build_initiator()`], e.g. if `server_public` isn't a 32-byte key.
+pub fn client(server_public: &[u8]) -> Result<HandshakeState, Error> {
+    let builder = Builder::new(params());
+    let local = builder.generate_keypair()?;
+    builder.local_keypair(&local)?.remote_public_key(server_public)?.build_initiator()
+}
+
+/// Build the responder of a handshake for clients made with [`client()`], using the static
+/// keypair `keypair`.
+///
+/// # Errors
+///
+/// Will result in the errors of [`Builder::build_responder()`], e.g. if `keypair` isn't a
+/// 25519 keypair.
+pub fn server(keypair: &Keypair) -> Result<HandshakeState, Error> {
+    Builder::new(params()).local_keypair(keypair)?.build_responder()
+}
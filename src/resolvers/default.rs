@@ -1,3 +1,4 @@
+use alloc::boxed::Box;
 use blake2::{Blake2b, Blake2b512, Blake2s, Blake2s256};
 #[cfg(feature = "xchachapoly")]
 use chacha20poly1305::XChaCha20Poly1305;
@@ -7,8 +8,12 @@ use curve25519_dalek::montgomery::MontgomeryPoint;
 use pqcrypto_kyber::kyber1024;
 #[cfg(feature = "pqclean_kyber1024")]
 use pqcrypto_traits::kem::{Ciphertext, PublicKey, SecretKey, SharedSecret};
+#[cfg(feature = "std")]
 use rand_core::OsRng;
 use sha2::{Digest, Sha256, Sha512};
+#[cfg(feature = "sha3")]
+use sha3::{Sha3_256, Sha3_512};
+use zeroize::Zeroize;
 
 use super::CryptoResolver;
 #[cfg(feature = "pqclean_kyber1024")]
@@ -30,13 +35,23 @@ use crate::{
 pub struct DefaultResolver;
 
 impl CryptoResolver for DefaultResolver {
+    #[cfg(feature = "std")]
     fn resolve_rng(&self) -> Option<Box<dyn Random>> {
         Some(Box::new(OsRng))
     }
 
+    /// There is no OS randomness without `std`; supply an RNG through another resolver.
+    #[cfg(not(feature = "std"))]
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        None
+    }
+
     fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn Dh>> {
         match *choice {
             DHChoice::Curve25519 => Some(Box::<Dh25519>::default()),
+            #[cfg(feature = "curve448")]
+            DHChoice::Curve448 => Some(Box::<Dh448>::default()),
+            #[cfg(not(feature = "curve448"))]
             DHChoice::Curve448 => None,
         }
     }
@@ -47,6 +62,12 @@ impl CryptoResolver for DefaultResolver {
             HashChoice::SHA512 => Some(Box::<HashSHA512>::default()),
             HashChoice::Blake2s => Some(Box::<HashBLAKE2s>::default()),
             HashChoice::Blake2b => Some(Box::<HashBLAKE2b>::default()),
+            #[cfg(feature = "sha3")]
+            HashChoice::SHA3_256 => Some(Box::<HashSHA3_256>::default()),
+            #[cfg(feature = "sha3")]
+            HashChoice::SHA3_512 => Some(Box::<HashSHA3_512>::default()),
+            #[cfg(feature = "blake3")]
+            HashChoice::Blake3 => Some(Box::<HashBLAKE3>::default()),
         }
     }
 
@@ -56,6 +77,10 @@ impl CryptoResolver for DefaultResolver {
             #[cfg(feature = "xchachapoly")]
             CipherChoice::XChaChaPoly => Some(Box::new(CipherXChaChaPoly::default())),
             CipherChoice::AESGCM => Some(Box::<CipherAesGcm>::default()),
+            #[cfg(feature = "aesgcmsiv")]
+            CipherChoice::AESGCMSIV => Some(Box::<CipherAesGcmSiv>::default()),
+            #[cfg(feature = "aegis")]
+            CipherChoice::AEGIS256 => Some(Box::<CipherAegis256>::default()),
         }
     }
 
@@ -74,18 +99,78 @@ struct Dh25519 {
     pubkey:  [u8; 32],
 }
 
+impl Drop for Dh25519 {
+    fn drop(&mut self) {
+        self.privkey.zeroize();
+    }
+}
+
+/// Wraps x448.
+#[cfg(feature = "curve448")]
+struct Dh448 {
+    privkey: [u8; 56],
+    pubkey:  [u8; 56],
+}
+
+#[cfg(feature = "curve448")]
+impl Drop for Dh448 {
+    fn drop(&mut self) {
+        self.privkey.zeroize();
+    }
+}
+
 /// Wraps `aes-gcm`'s AES256-GCM implementation.
 #[derive(Default)]
 struct CipherAesGcm {
     key: [u8; CIPHERKEYLEN],
 }
 
+impl Drop for CipherAesGcm {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// Wraps `aes-gcm-siv`'s AES256-GCM-SIV implementation.
+#[cfg(feature = "aesgcmsiv")]
+#[derive(Default)]
+struct CipherAesGcmSiv {
+    key: [u8; CIPHERKEYLEN],
+}
+
+#[cfg(feature = "aesgcmsiv")]
+impl Drop for CipherAesGcmSiv {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// Wraps `aegis`'s `Aegis256` implementation (RFC 9803 once published, draft-irtf-cfrg-aegis-aead).
+#[cfg(feature = "aegis")]
+#[derive(Default)]
+struct CipherAegis256 {
+    key: [u8; CIPHERKEYLEN],
+}
+
+#[cfg(feature = "aegis")]
+impl Drop for CipherAegis256 {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
 /// Wraps `chacha20_poly1305_aead`'s `ChaCha20Poly1305` implementation.
 #[derive(Default)]
 struct CipherChaChaPoly {
     key: [u8; CIPHERKEYLEN],
 }
 
+impl Drop for CipherChaChaPoly {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
 /// Wraps `chachapoly1305`'s XChaCha20Poly1305 implementation.
 #[cfg(feature = "xchachapoly")]
 #[derive(Default)]
@@ -93,6 +178,13 @@ struct CipherXChaChaPoly {
     key: [u8; CIPHERKEYLEN],
 }
 
+#[cfg(feature = "xchachapoly")]
+impl Drop for CipherXChaChaPoly {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
 /// Wraps `RustCrypto`'s SHA-256 implementation.
 struct HashSHA256 {
     hasher: Sha256,
@@ -115,6 +207,27 @@ struct HashBLAKE2s {
     hasher: Blake2s256,
 }
 
+/// Wraps `blake3`'s implementation.
+#[cfg(feature = "blake3")]
+#[derive(Default)]
+struct HashBLAKE3 {
+    hasher: blake3::Hasher,
+}
+
+/// Wraps `RustCrypto`'s SHA3-256 implementation.
+#[cfg(feature = "sha3")]
+#[derive(Default)]
+struct HashSHA3_256 {
+    hasher: Sha3_256,
+}
+
+/// Wraps `RustCrypto`'s SHA3-512 implementation.
+#[cfg(feature = "sha3")]
+#[derive(Default)]
+struct HashSHA3_512 {
+    hasher: Sha3_512,
+}
+
 /// Wraps `kyber1024`'s implementation
 #[cfg(feature = "pqclean_kyber1024")]
 struct Kyber1024 {
@@ -122,8 +235,6 @@ struct Kyber1024 {
     pubkey:  kyber1024::PublicKey,
 }
 
-impl Random for OsRng {}
-
 impl Dh25519 {
     fn derive_pubkey(&mut self) {
         let point = MontgomeryPoint::mul_base_clamped(self.privkey);
@@ -148,6 +259,7 @@ impl Dh for Dh25519 {
         let mut bytes = [0u8; CIPHERKEYLEN];
         copy_slices!(privkey, bytes);
         self.privkey = bytes;
+        bytes.zeroize();
         self.derive_pubkey();
     }
 
@@ -155,6 +267,7 @@ impl Dh for Dh25519 {
         let mut bytes = [0u8; CIPHERKEYLEN];
         rng.fill_bytes(&mut bytes);
         self.privkey = bytes;
+        bytes.zeroize();
         self.derive_pubkey();
     }
 
@@ -169,8 +282,71 @@ impl Dh for Dh25519 {
     fn dh(&self, pubkey: &[u8], out: &mut [u8]) -> Result<(), Error> {
         let mut pubkey_owned = [0u8; CIPHERKEYLEN];
         copy_slices!(&pubkey[..32], pubkey_owned);
-        let result = MontgomeryPoint(pubkey_owned).mul_clamped(self.privkey).to_bytes();
+        let mut result = MontgomeryPoint(pubkey_owned).mul_clamped(self.privkey).to_bytes();
         copy_slices!(result, out);
+        result.zeroize();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "curve448")]
+impl Default for Dh448 {
+    fn default() -> Self {
+        Dh448 { privkey: [0u8; 56], pubkey: [0u8; 56] }
+    }
+}
+
+#[cfg(feature = "curve448")]
+impl Dh448 {
+    fn derive_pubkey(&mut self) {
+        self.pubkey = x448::x448_unchecked(self.privkey, x448::X448_BASEPOINT_BYTES);
+    }
+}
+
+#[cfg(feature = "curve448")]
+impl Dh for Dh448 {
+    fn name(&self) -> &'static str {
+        "448"
+    }
+
+    fn pub_len(&self) -> usize {
+        56
+    }
+
+    fn priv_len(&self) -> usize {
+        56
+    }
+
+    fn set(&mut self, privkey: &[u8]) {
+        let mut bytes = [0u8; 56];
+        copy_slices!(privkey, bytes);
+        self.privkey = bytes;
+        bytes.zeroize();
+        self.derive_pubkey();
+    }
+
+    fn generate(&mut self, rng: &mut dyn Random) {
+        let mut bytes = [0u8; 56];
+        rng.fill_bytes(&mut bytes);
+        self.privkey = bytes;
+        bytes.zeroize();
+        self.derive_pubkey();
+    }
+
+    fn pubkey(&self) -> &[u8] {
+        &self.pubkey
+    }
+
+    fn privkey(&self) -> &[u8] {
+        &self.privkey
+    }
+
+    fn dh(&self, pubkey: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        let mut pubkey_owned = [0u8; 56];
+        copy_slices!(&pubkey[..56], pubkey_owned);
+        let mut result = x448::x448(self.privkey, pubkey_owned).ok_or(Error::Dh { token: None })?;
+        copy_slices!(result, out);
+        result.zeroize();
         Ok(())
     }
 }
@@ -224,7 +400,217 @@ impl Cipher for CipherAesGcm {
             ciphertext[message_len..].into(),
         )
         .map(|()| message_len)
-        .map_err(|_| Error::Decrypt)
+        .map_err(|_| Error::Decrypt { token: None })
+    }
+
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let tag = aes_gcm::Aes256Gcm::new(&self.key.into())
+            .encrypt_in_place_detached(&nonce_bytes.into(), authtext, &mut in_out[..plaintext_len])
+            .expect("Encryption failed!");
+
+        copy_slices!(tag, &mut in_out[plaintext_len..]);
+
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let message_len = in_out.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        let (message, tag) = in_out.split_at_mut(message_len);
+
+        aes_gcm::Aes256Gcm::new(&self.key.into())
+            .decrypt_in_place_detached(&nonce_bytes.into(), authtext, message, (&*tag).into())
+            .map(|()| message_len)
+            .map_err(|_| Error::Decrypt { token: None })
+    }
+}
+
+#[cfg(feature = "aesgcmsiv")]
+impl Cipher for CipherAesGcmSiv {
+    fn name(&self) -> &'static str {
+        "AESGCMSIV"
+    }
+
+    fn set(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        copy_slices!(key, &mut self.key);
+    }
+
+    fn encrypt(&self, nonce: u64, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) -> usize {
+        let aead = aes_gcm_siv::Aes256GcmSiv::new(&self.key.into());
+
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+
+        copy_slices!(plaintext, out);
+
+        let tag = aead
+            .encrypt_in_place_detached(&nonce_bytes.into(), authtext, &mut out[0..plaintext.len()])
+            .expect("Encryption failed!");
+
+        copy_slices!(tag, &mut out[plaintext.len()..]);
+
+        plaintext.len() + TAGLEN
+    }
+
+    fn decrypt(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let aead = aes_gcm_siv::Aes256GcmSiv::new(&self.key.into());
+
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+
+        let message_len = ciphertext.len() - TAGLEN;
+
+        copy_slices!(ciphertext[..message_len], out);
+
+        aead.decrypt_in_place_detached(
+            &nonce_bytes.into(),
+            authtext,
+            &mut out[..message_len],
+            ciphertext[message_len..].into(),
+        )
+        .map(|()| message_len)
+        .map_err(|_| Error::Decrypt { token: None })
+    }
+
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let tag = aes_gcm_siv::Aes256GcmSiv::new(&self.key.into())
+            .encrypt_in_place_detached(&nonce_bytes.into(), authtext, &mut in_out[..plaintext_len])
+            .expect("Encryption failed!");
+
+        copy_slices!(tag, &mut in_out[plaintext_len..]);
+
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let message_len = in_out.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        let (message, tag) = in_out.split_at_mut(message_len);
+
+        aes_gcm_siv::Aes256GcmSiv::new(&self.key.into())
+            .decrypt_in_place_detached(&nonce_bytes.into(), authtext, message, (&*tag).into())
+            .map(|()| message_len)
+            .map_err(|_| Error::Decrypt { token: None })
+    }
+}
+
+#[cfg(feature = "aegis")]
+impl CipherAegis256 {
+    fn nonce(nonce: u64) -> [u8; 32] {
+        let mut nonce_bytes = [0u8; 32];
+        copy_slices!(nonce.to_be_bytes(), &mut nonce_bytes[24..]);
+        nonce_bytes
+    }
+
+    fn seal(&self, nonce: u64, authtext: &[u8], in_out: &mut [u8]) -> [u8; TAGLEN] {
+        aegis::aegis256::Aegis256::<TAGLEN>::new(&self.key, &Self::nonce(nonce))
+            .encrypt_in_place(in_out, authtext)
+    }
+
+    fn open(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), Error> {
+        let tag = <&[u8; TAGLEN]>::try_from(tag).map_err(|_| Error::Decrypt { token: None })?;
+        aegis::aegis256::Aegis256::<TAGLEN>::new(&self.key, &Self::nonce(nonce))
+            .decrypt_in_place(in_out, tag, authtext)
+            .map_err(|_| {
+                // Cleared like the output of the other ciphers, rather than left as `aegis`'s
+                // filler bytes.
+                in_out.zeroize();
+                Error::Decrypt { token: None }
+            })
+    }
+}
+
+#[cfg(feature = "aegis")]
+impl Cipher for CipherAegis256 {
+    fn name(&self) -> &'static str {
+        "AEGIS256"
+    }
+
+    fn set(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        copy_slices!(key, &mut self.key);
+    }
+
+    fn encrypt(&self, nonce: u64, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) -> usize {
+        copy_slices!(plaintext, out);
+        self.encrypt_in_place(nonce, authtext, out, plaintext.len())
+    }
+
+    fn decrypt(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let message_len =
+            ciphertext.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        copy_slices!(ciphertext[..message_len], out);
+        self.open(nonce, authtext, &mut out[..message_len], &ciphertext[message_len..])?;
+        Ok(message_len)
+    }
+
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let tag = self.seal(nonce, authtext, &mut in_out[..plaintext_len]);
+        copy_slices!(tag, &mut in_out[plaintext_len..]);
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let message_len = in_out.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        let (message, tag) = in_out.split_at_mut(message_len);
+        self.open(nonce, authtext, message, tag)?;
+        Ok(message_len)
     }
 }
 
@@ -273,10 +659,45 @@ impl Cipher for CipherChaChaPoly {
                 &mut out[..message_len],
                 ciphertext[message_len..].into(),
             )
-            .map_err(|_| Error::Decrypt)?;
+            .map_err(|_| Error::Decrypt { token: None })?;
 
         Ok(message_len)
     }
+
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_le_bytes(), &mut nonce_bytes[4..]);
+        let tag = ChaCha20Poly1305::new(&self.key.into())
+            .encrypt_in_place_detached(&nonce_bytes.into(), authtext, &mut in_out[..plaintext_len])
+            .expect("Encryption failed!");
+
+        copy_slices!(tag, &mut in_out[plaintext_len..]);
+
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(nonce.to_le_bytes(), &mut nonce_bytes[4..]);
+        let message_len = in_out.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        let (message, tag) = in_out.split_at_mut(message_len);
+
+        ChaCha20Poly1305::new(&self.key.into())
+            .decrypt_in_place_detached(&nonce_bytes.into(), authtext, message, (&*tag).into())
+            .map(|()| message_len)
+            .map_err(|_| Error::Decrypt { token: None })
+    }
 }
 
 #[cfg(feature = "xchachapoly")]
@@ -325,10 +746,45 @@ impl Cipher for CipherXChaChaPoly {
                 &mut out[..message_len],
                 ciphertext[message_len..].into(),
             )
-            .map_err(|_| Error::Decrypt)?;
+            .map_err(|_| Error::Decrypt { token: None })?;
 
         Ok(message_len)
     }
+
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 24];
+        copy_slices!(&nonce.to_le_bytes(), &mut nonce_bytes[16..]);
+        let tag = XChaCha20Poly1305::new(&self.key.into())
+            .encrypt_in_place_detached(&nonce_bytes.into(), authtext, &mut in_out[..plaintext_len])
+            .expect("Encryption failed!");
+
+        copy_slices!(tag, &mut in_out[plaintext_len..]);
+
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut nonce_bytes = [0u8; 24];
+        copy_slices!(&nonce.to_le_bytes(), &mut nonce_bytes[16..]);
+        let message_len = in_out.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+        let (message, tag) = in_out.split_at_mut(message_len);
+
+        XChaCha20Poly1305::new(&self.key.into())
+            .decrypt_in_place_detached(&nonce_bytes.into(), authtext, message, (&*tag).into())
+            .map(|()| message_len)
+            .map_err(|_| Error::Decrypt { token: None })
+    }
 }
 
 impl Default for HashSHA256 {
@@ -451,6 +907,91 @@ impl Hash for HashBLAKE2s {
     }
 }
 
+#[cfg(feature = "blake3")]
+impl Hash for HashBLAKE3 {
+    fn name(&self) -> &'static str {
+        "BLAKE3"
+    }
+
+    fn block_len(&self) -> usize {
+        64
+    }
+
+    fn hash_len(&self) -> usize {
+        32
+    }
+
+    fn reset(&mut self) {
+        self.hasher.reset();
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        let hash = self.hasher.finalize();
+        self.hasher.reset();
+        copy_slices!(hash.as_bytes(), out);
+    }
+}
+
+#[cfg(feature = "sha3")]
+impl Hash for HashSHA3_256 {
+    fn name(&self) -> &'static str {
+        "SHA3/256"
+    }
+
+    fn block_len(&self) -> usize {
+        136
+    }
+
+    fn hash_len(&self) -> usize {
+        32
+    }
+
+    fn reset(&mut self) {
+        self.hasher = Sha3_256::new();
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        let hash = self.hasher.finalize_reset();
+        copy_slices!(hash.as_slice(), out);
+    }
+}
+
+#[cfg(feature = "sha3")]
+impl Hash for HashSHA3_512 {
+    fn name(&self) -> &'static str {
+        "SHA3/512"
+    }
+
+    fn block_len(&self) -> usize {
+        72
+    }
+
+    fn hash_len(&self) -> usize {
+        64
+    }
+
+    fn reset(&mut self) {
+        self.hasher = Sha3_512::new();
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        let hash = self.hasher.finalize_reset();
+        copy_slices!(hash.as_slice(), out);
+    }
+}
+
 #[cfg(feature = "pqclean_kyber1024")]
 impl Default for Kyber1024 {
     fn default() -> Self {
@@ -537,6 +1078,44 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_blake3() {
+        // BLAKE3 test - reference implementation
+        let mut output = [0u8; 32];
+        let mut hasher = HashBLAKE3::default();
+        hasher.input(b"abc");
+        hasher.result(&mut output);
+        assert_eq!(
+            hex::encode(output),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn test_sha3() {
+        // SHA3 test - FIPS 202 examples
+        let mut output = [0u8; 32];
+        let mut hasher = HashSHA3_256::default();
+        hasher.input(b"abc");
+        hasher.result(&mut output);
+        assert_eq!(
+            hex::encode(output),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+
+        let mut output = [0u8; 64];
+        let mut hasher = HashSHA3_512::default();
+        hasher.input(b"abc");
+        hasher.result(&mut output);
+        assert_eq!(
+            hex::encode(output),
+            "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e\
+             10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"
+        );
+    }
+
     #[test]
     fn test_hmac_sha256_sha512() {
         let key = Vec::<u8>::from_hex("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
@@ -613,6 +1192,34 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "curve448")]
+    fn test_curve448() {
+        // Curve448 test - RFC 7748 Section 5.2
+        let mut keypair = Dh448::default();
+        let scalar = Vec::<u8>::from_hex(
+            "3d262fddf9ec8e88495266fea19a34d28882acef045104d0d1aae121\
+             700a779c984c24f8cdd78fbff44943eba368f54b29259a4f1c600ad3",
+        )
+        .unwrap();
+        keypair.set(&scalar);
+        let public = Vec::<u8>::from_hex(
+            "06fce640fa3487bfda5f6cf2d5263f8aad88334cbd07437f020f08f9\
+             814dc031ddbdc38c19c6da2583fa5429db94ada18aa7a7fb4ef8a086",
+        )
+        .unwrap();
+        let mut output = [0u8; 56];
+        keypair.dh(&public, &mut output).unwrap();
+        assert_eq!(
+            hex::encode(output),
+            "ce3e4ff95a60dc6697da1db1d85e6afbdf79b50a2412d7546d5f239f\
+             e14fbaadeb445fc66a01b0779d98223961111e21766282f73dd96b6f"
+        );
+
+        // low-order points are rejected
+        assert!(keypair.dh(&[0u8; 56], &mut output).is_err());
+    }
+
     #[test]
     fn test_aesgcm() {
         // AES256-GCM tests - gcm-spec.pdf
@@ -715,6 +1322,81 @@ mod tests {
         assert!(hex::encode(resulttext.to_vec()) == hex::encode(plaintext.to_vec()));
     }
 
+    #[cfg(feature = "aesgcmsiv")]
+    #[test]
+    fn test_aesgcmsiv_roundtrip() {
+        // AES256-GCM-SIV round-trip test, with tampering
+        let key = [1u8; 32];
+        let nonce = 3u64;
+        let plaintext = [0x34u8; 117];
+        let authtext = [0x12u8; 7];
+        let mut ciphertext = [0u8; 133];
+        let mut cipher1 = CipherAesGcmSiv::default();
+        cipher1.set(&key);
+        assert_eq!(cipher1.encrypt(nonce, &authtext, &plaintext, &mut ciphertext), 133);
+
+        let mut resulttext = [0u8; 117];
+        let mut cipher2 = CipherAesGcmSiv::default();
+        cipher2.set(&key);
+        assert_eq!(cipher2.decrypt(nonce, &authtext, &ciphertext, &mut resulttext).unwrap(), 117);
+        assert_eq!(resulttext, plaintext);
+        assert!(cipher2.decrypt(nonce + 1, &authtext, &ciphertext, &mut resulttext).is_err());
+        ciphertext[0] ^= 1;
+        assert!(cipher2.decrypt(nonce, &authtext, &ciphertext, &mut resulttext).is_err());
+    }
+
+    #[cfg(feature = "aegis")]
+    #[test]
+    fn test_aegis256_known_answer() {
+        // draft-irtf-cfrg-aegis-aead, AEGIS-256 test vectors 1 and 2.
+        let key = <[u8; 32]>::from_hex(
+            "10010000000000000000000000000000\
+             00000000000000000000000000000000",
+        )
+        .unwrap();
+        let nonce = <[u8; 32]>::from_hex(
+            "10000200000000000000000000000000\
+             00000000000000000000000000000000",
+        )
+        .unwrap();
+        // The vectors' nonces don't fit the Noise nonce layout, so this checks `aegis` directly.
+        let mut message = [0u8; 16];
+        let tag = aegis::aegis256::Aegis256::<TAGLEN>::new(&key, &nonce)
+            .encrypt_in_place(&mut message, &[]);
+        assert_eq!(hex::encode(message), "754fc3d8c973246dcc6d741412a4b236");
+        assert_eq!(hex::encode(tag), "3fe91994768b332ed7f570a19ec5896e");
+        aegis::aegis256::Aegis256::<TAGLEN>::new(&key, &nonce)
+            .decrypt_in_place(&mut message, &tag, &[])
+            .unwrap();
+        assert_eq!(message, [0u8; 16]);
+
+        let tag =
+            aegis::aegis256::Aegis256::<TAGLEN>::new(&key, &nonce).encrypt_in_place(&mut [], &[]);
+        assert_eq!(hex::encode(tag), "e3def978a0f054afd1e761d7553afba3");
+    }
+
+    #[cfg(feature = "aegis")]
+    #[test]
+    fn test_aegis256_roundtrip() {
+        // Partial blocks of both authtext and plaintext, with tampering.
+        let key = [1u8; 32];
+        let nonce = 3u64;
+        let plaintext = [0x34u8; 117];
+        let authtext = [0x12u8; 7];
+        let mut ciphertext = [0u8; 133];
+        let mut cipher = CipherAegis256::default();
+        cipher.set(&key);
+        assert_eq!(cipher.encrypt(nonce, &authtext, &plaintext, &mut ciphertext), 133);
+
+        let mut resulttext = [0u8; 117];
+        assert_eq!(cipher.decrypt(nonce, &authtext, &ciphertext, &mut resulttext).unwrap(), 117);
+        assert_eq!(resulttext, plaintext);
+        assert!(cipher.decrypt(nonce + 1, &authtext, &ciphertext, &mut resulttext).is_err());
+        assert_eq!(resulttext, [0u8; 117]);
+        ciphertext[116] ^= 1;
+        assert!(cipher.decrypt(nonce, &authtext, &ciphertext, &mut resulttext).is_err());
+    }
+
     #[test]
     fn test_chachapoly_known_answer() {
         //ChaChaPoly known-answer test - RFC 7539
@@ -827,4 +1509,70 @@ mod tests {
         assert_eq!(ss1_len, ss2_len);
         assert_eq!(ct_len, ciphertext.len());
     }
+
+    /// Forwards only the required methods, so the trait's default in-place methods are used.
+    struct DetachedOnly(CipherChaChaPoly);
+
+    impl Cipher for DetachedOnly {
+        fn name(&self) -> &'static str {
+            self.0.name()
+        }
+
+        fn set(&mut self, key: &[u8; CIPHERKEYLEN]) {
+            self.0.set(key);
+        }
+
+        fn encrypt(&self, nonce: u64, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) -> usize {
+            self.0.encrypt(nonce, authtext, plaintext, out)
+        }
+
+        fn decrypt(
+            &self,
+            nonce: u64,
+            authtext: &[u8],
+            ciphertext: &[u8],
+            out: &mut [u8],
+        ) -> Result<usize, Error> {
+            self.0.decrypt(nonce, authtext, ciphertext, out)
+        }
+    }
+
+    fn check_in_place(cipher: &mut dyn Cipher) {
+        let key = [7u8; 32];
+        let nonce = 5u64;
+        let plaintext = [0x34u8; 117];
+        let authtext = [0x12u8; 7];
+        cipher.set(&key);
+
+        let mut ciphertext = [0u8; 133];
+        assert_eq!(cipher.encrypt(nonce, &authtext, &plaintext, &mut ciphertext), 133);
+
+        let mut in_out = [0u8; 133];
+        in_out[..117].copy_from_slice(&plaintext);
+        assert_eq!(cipher.encrypt_in_place(nonce, &authtext, &mut in_out, 117), 133);
+        assert_eq!(in_out, ciphertext);
+
+        assert_eq!(cipher.decrypt_in_place(nonce, &authtext, &mut in_out).unwrap(), 117);
+        assert_eq!(in_out[..117], plaintext);
+
+        ciphertext[0] ^= 1;
+        assert!(cipher.decrypt_in_place(nonce, &authtext, &mut ciphertext).is_err());
+        assert!(matches!(
+            cipher.decrypt_in_place(nonce, &authtext, &mut [0u8; TAGLEN - 1]),
+            Err(Error::Decrypt { token: None })
+        ));
+    }
+
+    #[test]
+    fn test_in_place_matches_detached() {
+        check_in_place(&mut CipherChaChaPoly::default());
+        check_in_place(&mut CipherAesGcm::default());
+        #[cfg(feature = "xchachapoly")]
+        check_in_place(&mut CipherXChaChaPoly::default());
+        #[cfg(feature = "aesgcmsiv")]
+        check_in_place(&mut CipherAesGcmSiv::default());
+        #[cfg(feature = "aegis")]
+        check_in_place(&mut CipherAegis256::default());
+        check_in_place(&mut DetachedOnly(CipherChaChaPoly::default()));
+    }
 }
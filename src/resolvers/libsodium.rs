@@ -123,7 +123,7 @@ impl Dh for SodiumDh25519 {
                 copy_slices!(buf.as_ref(), out);
                 Ok(())
             },
-            Err(_) => Err(Error::Dh),
+            Err(_) => Err(Error::Dh { token: None }),
         }
     }
 }
@@ -180,7 +180,7 @@ impl Cipher for SodiumChaChaPoly {
                 copy_slices!(&buf, out);
                 Ok(buf.len())
             },
-            Err(_) => Err(Error::Decrypt),
+            Err(_) => Err(Error::Decrypt { token: None }),
         }
     }
 }
@@ -256,8 +256,6 @@ impl rand_core::RngCore for SodiumRng {
 
 impl rand_core::CryptoRng for SodiumRng {}
 
-impl Random for SodiumRng {}
-
 #[cfg(test)]
 mod tests {
     extern crate hex;
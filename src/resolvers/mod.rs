@@ -18,6 +18,7 @@ use crate::{
     params::{CipherChoice, DHChoice, HashChoice},
     types::{Cipher, Dh, Hash, Random},
 };
+use alloc::boxed::Box;
 
 #[cfg(feature = "default-resolver")]
 pub use self::default::DefaultResolver;
@@ -30,18 +31,71 @@ pub use self::ring::RingResolver;
 pub type BoxedCryptoResolver = Box<dyn CryptoResolver + Send>;
 
 /// An object that resolves the providers of Noise crypto choices
+///
+/// Every method defaults to resolving nothing, so a resolver that only swaps out some
+/// primitives can implement just those, and be wrapped in a [`FallbackResolver`] to take the
+/// rest from another resolver:
+///
+/// ```
+/// # use snow::{resolvers::*, types::Random, Builder};
+/// # #[cfg(feature = "default-resolver")]
+/// # fn try_main() -> Result<(), snow::Error> {
+/// struct MyRng;
+///
+/// impl rand_core::RngCore for MyRng {
+///     // ...
+/// #     fn next_u32(&mut self) -> u32 { 4 }
+/// #     fn next_u64(&mut self) -> u64 { 4 }
+/// #     fn fill_bytes(&mut self, dest: &mut [u8]) { dest.fill(4) }
+/// #     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+/// #         self.fill_bytes(dest);
+/// #         Ok(())
+/// #     }
+/// }
+/// impl rand_core::CryptoRng for MyRng {}
+///
+/// struct MyRngResolver;
+///
+/// impl CryptoResolver for MyRngResolver {
+///     fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+///         Some(Box::new(MyRng))
+///     }
+/// }
+///
+/// let resolver = FallbackResolver::new(Box::new(MyRngResolver), Box::new(DefaultResolver));
+/// let params = "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?;
+/// let builder = Builder::with_resolver(params, Box::new(resolver));
+/// # let _ = builder.build_initiator()?;
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "default-resolver"))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
 pub trait CryptoResolver {
     /// Provide an implementation of the Random trait or None if none available.
-    fn resolve_rng(&self) -> Option<Box<dyn Random>>;
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        None
+    }
 
     /// Provide an implementation of the Dh trait for the given `DHChoice` or None if unavailable.
-    fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn Dh>>;
+    fn resolve_dh(&self, _choice: &DHChoice) -> Option<Box<dyn Dh>> {
+        None
+    }
 
     /// Provide an implementation of the Hash trait for the given `HashChoice` or None if unavailable.
-    fn resolve_hash(&self, choice: &HashChoice) -> Option<Box<dyn Hash>>;
+    fn resolve_hash(&self, _choice: &HashChoice) -> Option<Box<dyn Hash>> {
+        None
+    }
 
     /// Provide an implementation of the Cipher trait for the given `CipherChoice` or None if unavailable.
-    fn resolve_cipher(&self, choice: &CipherChoice) -> Option<Box<dyn Cipher>>;
+    fn resolve_cipher(&self, _choice: &CipherChoice) -> Option<Box<dyn Cipher>> {
+        None
+    }
 
     /// Provide an implementation of the Kem trait for the given KemChoice or None if unavailable
     #[cfg(feature = "hfs")]
@@ -41,6 +41,10 @@ impl CryptoResolver for RingResolver {
             CipherChoice::ChaChaPoly => Some(Box::new(CipherChaChaPoly::default())),
             #[cfg(feature = "xchachapoly")]
             CipherChoice::XChaChaPoly => None,
+            #[cfg(feature = "aesgcmsiv")]
+            CipherChoice::AESGCMSIV => None,
+            #[cfg(feature = "aegis")]
+            CipherChoice::AEGIS256 => None,
         }
     }
 }
@@ -75,8 +79,6 @@ impl rand_core::RngCore for RingRng {
 
 impl rand_core::CryptoRng for RingRng {}
 
-impl Random for RingRng {}
-
 struct CipherAESGCM {
     // NOTE: LessSafeKey is chosen here because nonce atomicity is handled outside of this structure.
     // See ring documentation for more details on the naming choices.
@@ -139,21 +141,71 @@ impl Cipher for CipherAESGCM {
             let len = self
                 .key
                 .open_in_place(nonce, aead::Aad::from(authtext), in_out)
-                .map_err(|_| Error::Decrypt)?
+                .map_err(|_| Error::Decrypt { token: None })?
                 .len();
 
             Ok(len)
         } else {
-            let mut in_out = ciphertext.to_vec();
+            // `out` only has room for the plaintext, so open it there with the tag kept apart.
+            let message_len =
+                ciphertext.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+            let (message, tag) = ciphertext.split_at(message_len);
+            let tag = aead::Tag::try_from(tag).map_err(|_| Error::Decrypt { token: None })?;
+            let in_out = &mut out[..message_len];
+            in_out.copy_from_slice(message);
+
+            self.key
+                .open_in_place_separate_tag(nonce, aead::Aad::from(authtext), tag, in_out, 0..)
+                .map_err(|_| Error::Decrypt { token: None })?;
+
+            Ok(message_len)
+        }
+    }
 
-            let out0 = self
-                .key
-                .open_in_place(nonce, aead::Aad::from(authtext), &mut in_out)
-                .map_err(|_| Error::Decrypt)?;
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(&nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
+
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(
+                nonce,
+                aead::Aad::from(authtext),
+                &mut in_out[..plaintext_len],
+            )
+            .unwrap();
+        in_out[plaintext_len..plaintext_len + TAGLEN].copy_from_slice(tag.as_ref());
+
+        plaintext_len + TAGLEN
+    }
 
-            out[..out0.len()].copy_from_slice(out0);
-            Ok(out0.len())
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if in_out.len() < TAGLEN {
+            return Err(Error::Decrypt { token: None });
         }
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(&nonce.to_be_bytes(), &mut nonce_bytes[4..]);
+        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
+
+        let len = self
+            .key
+            .open_in_place(nonce, aead::Aad::from(authtext), in_out)
+            .map_err(|_| Error::Decrypt { token: None })?
+            .len();
+
+        Ok(len)
     }
 }
 
@@ -218,21 +270,71 @@ impl Cipher for CipherChaChaPoly {
             let len = self
                 .key
                 .open_in_place(nonce, aead::Aad::from(authtext), in_out)
-                .map_err(|_| Error::Decrypt)?
+                .map_err(|_| Error::Decrypt { token: None })?
                 .len();
 
             Ok(len)
         } else {
-            let mut in_out = ciphertext.to_vec();
+            // `out` only has room for the plaintext, so open it there with the tag kept apart.
+            let message_len =
+                ciphertext.len().checked_sub(TAGLEN).ok_or(Error::Decrypt { token: None })?;
+            let (message, tag) = ciphertext.split_at(message_len);
+            let tag = aead::Tag::try_from(tag).map_err(|_| Error::Decrypt { token: None })?;
+            let in_out = &mut out[..message_len];
+            in_out.copy_from_slice(message);
+
+            self.key
+                .open_in_place_separate_tag(nonce, aead::Aad::from(authtext), tag, in_out, 0..)
+                .map_err(|_| Error::Decrypt { token: None })?;
+
+            Ok(message_len)
+        }
+    }
 
-            let out0 = self
-                .key
-                .open_in_place(nonce, aead::Aad::from(authtext), &mut in_out)
-                .map_err(|_| Error::Decrypt)?;
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(&nonce.to_le_bytes(), &mut nonce_bytes[4..]);
+        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
 
-            out[..out0.len()].copy_from_slice(out0);
-            Ok(out0.len())
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(
+                nonce,
+                aead::Aad::from(authtext),
+                &mut in_out[..plaintext_len],
+            )
+            .unwrap();
+        in_out[plaintext_len..plaintext_len + TAGLEN].copy_from_slice(tag.as_ref());
+
+        plaintext_len + TAGLEN
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if in_out.len() < TAGLEN {
+            return Err(Error::Decrypt { token: None });
         }
+        let mut nonce_bytes = [0u8; 12];
+        copy_slices!(&nonce.to_le_bytes(), &mut nonce_bytes[4..]);
+        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
+
+        let len = self
+            .key
+            .open_in_place(nonce, aead::Aad::from(authtext), in_out)
+            .map_err(|_| Error::Decrypt { token: None })?
+            .len();
+
+        Ok(len)
     }
 }
 struct HashSHA256 {
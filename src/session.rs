@@ -0,0 +1,176 @@
+use crate::{
+    error::{Error, StateProblem},
+    handshakestate::HandshakeState,
+    transportstate::TransportState,
+};
+use core::convert::TryFrom;
+
+/// A Noise session in either of its two phases, for applications that would rather keep one
+/// value around than track which of [`HandshakeState`] and [`TransportState`] they hold.
+///
+/// `write_message()` and `read_message()` work the same way in both phases, and the switch
+/// from handshake to transport is an explicit [`into_transport_mode()`](Self::into_transport_mode)
+/// call that fails if the handshake isn't finished yet.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{Builder, Session};
+/// #
+/// # #[cfg(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated")))]
+/// # fn try_main() -> Result<(), snow::Error> {
+/// let params: snow::params::NoiseParams = "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?;
+/// let mut initiator = Session::from(Builder::new(params.clone()).build_initiator()?);
+/// let mut responder = Session::from(Builder::new(params).build_responder()?);
+/// let (mut message, mut payload) = ([0u8; 1024], [0u8; 1024]);
+///
+/// while !initiator.is_handshake_finished() {
+///     let len = initiator.write_message(&[], &mut message)?;
+///     responder.read_message(&message[..len], &mut payload)?;
+///     if !responder.is_handshake_finished() {
+///         let len = responder.write_message(&[], &mut message)?;
+///         initiator.read_message(&message[..len], &mut payload)?;
+///     }
+/// }
+///
+/// let mut initiator = initiator.into_transport_mode()?;
+/// let len = initiator.write_message(b"hello", &mut message)?;
+/// # let mut responder = responder.into_transport_mode()?;
+/// # let len = responder.read_message(&message[..len], &mut payload)?;
+/// # assert_eq!(&payload[..len], b"hello");
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated"))))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+// Boxing the handshake would only save memory once the session has moved into the transport
+// phase, at the cost of an allocation for every session.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Session {
+    /// The session is still in the handshake phase.
+    Handshake(HandshakeState),
+    /// The handshake is done and the session is in the transport phase.
+    Transport(TransportState),
+}
+
+impl Session {
+    /// Write a handshake or transport message with `payload` to `message`, depending on the
+    /// phase.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// As [`HandshakeState::write_message()`] or [`TransportState::write_message()`].
+    pub fn write_message(&mut self, payload: &[u8], message: &mut [u8]) -> Result<usize, Error> {
+        match self {
+            Session::Handshake(state) => state.write_message(payload, message),
+            Session::Transport(state) => state.write_message(payload, message),
+        }
+    }
+
+    /// Read a handshake or transport message from `message` into `payload`, depending on the
+    /// phase.
+    ///
+    /// Returns the number of bytes written to `payload`.
+    ///
+    /// # Errors
+    ///
+    /// As [`HandshakeState::read_message()`] or [`TransportState::read_message()`].
+    pub fn read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
+        match self {
+            Session::Handshake(state) => state.read_message(message, payload),
+            Session::Transport(state) => state.read_message(message, payload),
+        }
+    }
+
+    /// Move the session into the transport phase once the handshake is finished.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::HandshakeNotFinished` if the handshake isn't finished,
+    /// and `StateProblem::HandshakeAlreadyFinished` if the session is already in the transport
+    /// phase. Either way the session is consumed, so check
+    /// [`is_handshake_finished()`](Self::is_handshake_finished) first.
+    pub fn into_transport_mode(self) -> Result<Self, Error> {
+        match self {
+            Session::Handshake(state) if state.is_handshake_finished() => {
+                Ok(Session::Transport(state.into_transport_mode()?))
+            },
+            Session::Handshake(_) => Err(StateProblem::HandshakeNotFinished.into()),
+            Session::Transport(_) => Err(StateProblem::HandshakeAlreadyFinished.into()),
+        }
+    }
+
+    /// Check if the handshake is finished, i.e. whether the session is in the transport phase
+    /// or can be moved into it.
+    #[must_use]
+    pub fn is_handshake_finished(&self) -> bool {
+        match self {
+            Session::Handshake(state) => state.is_handshake_finished(),
+            Session::Transport(_) => true,
+        }
+    }
+
+    /// Check if the session is in the transport phase.
+    #[must_use]
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Session::Transport(_))
+    }
+
+    /// Check if this session was started with the "initiator" role.
+    #[must_use]
+    pub fn is_initiator(&self) -> bool {
+        match self {
+            Session::Handshake(state) => state.is_initiator(),
+            Session::Transport(state) => state.is_initiator(),
+        }
+    }
+
+    /// Get the remote party's static public key, if known yet.
+    #[must_use]
+    pub fn get_remote_static(&self) -> Option<&[u8]> {
+        match self {
+            Session::Handshake(state) => state.get_remote_static(),
+            Session::Transport(state) => state.get_remote_static(),
+        }
+    }
+
+    /// Get the handshake hash, which is only final once the handshake is finished.
+    #[must_use]
+    pub fn get_handshake_hash(&self) -> &[u8] {
+        match self {
+            Session::Handshake(state) => state.get_handshake_hash(),
+            Session::Transport(state) => state.get_handshake_hash(),
+        }
+    }
+}
+
+impl From<HandshakeState> for Session {
+    fn from(state: HandshakeState) -> Self {
+        Session::Handshake(state)
+    }
+}
+
+impl From<TransportState> for Session {
+    fn from(state: TransportState) -> Self {
+        Session::Transport(state)
+    }
+}
+
+impl TryFrom<Session> for TransportState {
+    type Error = Error;
+
+    fn try_from(session: Session) -> Result<Self, Self::Error> {
+        match session {
+            Session::Handshake(state) => state.into_transport_mode(),
+            Session::Transport(state) => Ok(state),
+        }
+    }
+}
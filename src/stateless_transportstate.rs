@@ -1,12 +1,83 @@
 use crate::{
     cipherstate::StatelessCipherStates,
-    constants::{CIPHERKEYLEN, MAXDHLEN, MAXMSGLEN, TAGLEN},
+    constants::{CIPHERKEYLEN, MAXDHLEN, MAXHASHLEN, PSKLEN, TAGLEN},
     error::{Error, StateProblem},
     handshakestate::HandshakeState,
+    observer::Observer,
     params::HandshakePattern,
+    symmetricstate::Exporter,
     utils::Toggle,
 };
-use std::{convert::TryFrom, fmt};
+use alloc::vec::Vec;
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+use zeroize::Zeroizing;
+
+/// A sliding window of the nonces received by a [`StatelessTransportState`], used to reject
+/// duplicated or replayed messages, as in IPsec and WireGuard.
+///
+/// The window tracks the highest nonce accepted and the 128 nonces below it. A nonce is fresh
+/// if it is above the highest one or is in the window and hasn't been seen yet; anything older
+/// than the window is rejected. A window only covers one direction of one session, so keep a
+/// separate one per [`StatelessTransportState`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ReplayWindow {
+    /// One more than the highest nonce accepted, or zero if none has been.
+    next:   u64,
+    /// Bit `i` is set if nonce `next - 1 - i` has been accepted.
+    bitmap: u128,
+}
+
+impl ReplayWindow {
+    /// Create an empty window.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether `nonce` would be accepted, without recording it.
+    #[must_use]
+    pub fn is_fresh(&self, nonce: u64) -> bool {
+        if nonce == u64::MAX {
+            false
+        } else if nonce >= self.next {
+            true
+        } else {
+            let offset = self.next - 1 - nonce;
+            offset < u128::BITS.into() && self.bitmap & (1 << offset) == 0
+        }
+    }
+
+    /// Record `nonce` as received.
+    ///
+    /// Only record a nonce once the message carrying it has been authenticated, otherwise a
+    /// forged message could shut out the real one.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::Replayed` if the nonce isn't fresh.
+    pub fn accept(&mut self, nonce: u64) -> Result<(), Error> {
+        if !self.is_fresh(nonce) {
+            return Err(StateProblem::Replayed.into());
+        }
+        if nonce >= self.next {
+            let shift = nonce + 1 - self.next;
+            self.bitmap =
+                self.bitmap.checked_shl(shift.try_into().unwrap_or(u32::MAX)).unwrap_or(0);
+            self.bitmap |= 1;
+            self.next = nonce + 1;
+        } else {
+            self.bitmap |= 1 << (self.next - 1 - nonce);
+        }
+        Ok(())
+    }
+}
 
 /// A state machine encompassing the transport phase of a Noise session, using the two
 /// `CipherState`s (for sending and receiving) that were spawned from the `SymmetricState`'s
@@ -14,11 +85,16 @@ use std::{convert::TryFrom, fmt};
 ///
 /// See: <https://noiseprotocol.org/noise.html#the-handshakestate-object>
 pub struct StatelessTransportState {
-    cipherstates: StatelessCipherStates,
-    pattern:      HandshakePattern,
-    dh_len:       usize,
-    rs:           Toggle<[u8; MAXDHLEN]>,
-    initiator:    bool,
+    cipherstates:    StatelessCipherStates,
+    pattern:         HandshakePattern,
+    dh_len:          usize,
+    rs:              Toggle<[u8; MAXDHLEN]>,
+    initiator:       bool,
+    h:               Zeroizing<[u8; MAXHASHLEN]>,
+    h_len:           usize,
+    exporter:        Exporter,
+    max_message_len: usize,
+    observer:        Observer,
 }
 
 impl StatelessTransportState {
@@ -28,10 +104,39 @@ impl StatelessTransportState {
         }
 
         let dh_len = handshake.dh_len();
-        let HandshakeState { cipherstates, params, rs, initiator, .. } = handshake;
+        let (h, h_len) = handshake.handshake_hash_buf();
+        let HandshakeState {
+            cipherstates,
+            params,
+            rs,
+            initiator,
+            symmetricstate,
+            max_message_len,
+            observer,
+            ..
+        } = handshake;
         let pattern = params.handshake.pattern;
+        observer.transport_ready(&h[..h_len]);
+
+        Ok(Self {
+            cipherstates: cipherstates.into(),
+            pattern,
+            dh_len,
+            rs,
+            initiator,
+            h,
+            h_len,
+            exporter: symmetricstate.into_exporter(),
+            max_message_len,
+            observer,
+        })
+    }
 
-        Ok(Self { cipherstates: cipherstates.into(), pattern, dh_len, rs, initiator })
+    /// The number of bytes every transport message adds on top of its payload, i.e. the size
+    /// of the authentication tag.
+    #[must_use]
+    pub const fn message_overhead(&self) -> usize {
+        TAGLEN
     }
 
     /// Get the remote party's static public key, if available.
@@ -45,6 +150,56 @@ impl StatelessTransportState {
         self.rs.get().map(|rs| &rs[..self.dh_len])
     }
 
+    /// Get the final handshake hash, e.g. for channel binding.
+    ///
+    /// Returns a slice of length `Hasher.hash_len()` (i.e. HASHLEN for the chosen Hash function).
+    ///
+    /// See: <https://noiseprotocol.org/noise.html#channel-binding>
+    #[must_use]
+    pub fn get_handshake_hash(&self) -> &[u8] {
+        &self.h[..self.h_len]
+    }
+
+    /// Derive `out.len()` bytes of keying material bound to this session, e.g. to key a
+    /// subsystem outside of Noise, in the style of a TLS exporter.
+    ///
+    /// The output is `HKDF(exporter_secret, len(label) || label || context)`, truncated to
+    /// `out.len()`, where `exporter_secret = HKDF(ck, h)` is taken from the final chaining key
+    /// and handshake hash, and `len(label)` is 2 bytes big-endian. Both peers derive the same
+    /// material for the same `label` and `context`, and it reveals nothing about the transport
+    /// keys. Use a distinct `label` for each purpose.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `out` is longer than the hash length of the session
+    /// or `label` is longer than 65535 bytes.
+    pub fn export_keying_material(
+        &mut self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        self.exporter.export(label, context, out)
+    }
+
+    /// Derive a secret that lets the peers resume this session later with a cheaper
+    /// handshake, like a TLS session ticket: pass it to
+    /// [`Builder::resumption_psk()`](crate::Builder::resumption_psk) of a `psk` pattern such as
+    /// `NNpsk0`, and only someone who took part in this session can complete that handshake.
+    /// `context` binds the secret to e.g. a ticket identifier, and must match on both sides.
+    ///
+    /// The secret is an output of [`export_keying_material()`](Self::export_keying_material)
+    /// under its own label. Use each secret for only one resumption: the first message of a
+    /// `psk0` handshake can be replayed, so drop a secret once it's been used.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if the session's hash is shorter than a PSK, which is
+    /// never the case for the hashes in the Noise specification.
+    pub fn resumption_secret(&mut self, context: &[u8]) -> Result<Zeroizing<[u8; PSKLEN]>, Error> {
+        self.exporter.resumption_secret(context)
+    }
+
     /// Construct a message from `payload` (and pending handshake tokens if in handshake state),
     /// and write it to the `message` buffer.
     ///
@@ -53,7 +208,8 @@ impl StatelessTransportState {
     /// # Errors
     ///
     /// Will result in `Error::Input` if the size of the output exceeds the max message
-    /// length in the Noise Protocol (65535 bytes).
+    /// length in the Noise Protocol (65535 bytes), or the one set with
+    /// `Builder::max_message_len()`.
     pub fn write_message(
         &self,
         nonce: u64,
@@ -62,12 +218,38 @@ impl StatelessTransportState {
     ) -> Result<usize, Error> {
         if !self.initiator && self.pattern.is_oneway() {
             return Err(StateProblem::OneWay.into());
-        } else if payload.len() + TAGLEN > MAXMSGLEN || payload.len() + TAGLEN > message.len() {
+        } else if payload.len() + TAGLEN > self.max_message_len
+            || payload.len() + TAGLEN > message.len()
+        {
             return Err(Error::Input);
         }
 
         let cipher = if self.initiator { &self.cipherstates.0 } else { &self.cipherstates.1 };
-        cipher.encrypt(nonce, payload, message)
+        let len = cipher.encrypt(nonce, payload, message)?;
+        self.observer.message_written(len);
+        Ok(len)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is the concatenation of
+    /// the `payload` fragments, which are encrypted as a single Noise message without first
+    /// being joined into a separate buffer.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` can't hold the payload and the rest of the
+    /// message, or if the size of the output exceeds the max message length in the Noise
+    /// Protocol (65535 bytes), or the one set with `Builder::max_message_len()`.
+    #[cfg(feature = "std")]
+    pub fn write_message_vectored(
+        &self,
+        nonce: u64,
+        payload: &[IoSlice<'_>],
+        message: &mut [u8],
+    ) -> Result<usize, Error> {
+        let payload_len = crate::utils::gather(payload, message)?;
+        self.write_message_in_place(nonce, message, payload_len)
     }
 
     /// Read a noise message from `message` and write the payload to the `payload` buffer.
@@ -75,7 +257,8 @@ impl StatelessTransportState {
     /// Returns the number of bytes written to `payload`.
     ///
     /// # Errors
-    /// Will result in `Error::Input` if the message is more than 65535 bytes.
+    /// Will result in `Error::Input` if the message is longer than the max message length
+    /// (65535 bytes, or the one set with `Builder::max_message_len()`).
     ///
     /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
     /// authentication tag didn't verify.
@@ -84,17 +267,187 @@ impl StatelessTransportState {
     pub fn read_message(
         &self,
         nonce: u64,
-        payload: &[u8],
+        message: &[u8],
+        payload: &mut [u8],
+    ) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
+        } else if self.initiator && self.pattern.is_oneway() {
+            return Err(StateProblem::OneWay.into());
+        }
+        let cipher = if self.initiator { &self.cipherstates.1 } else { &self.cipherstates.0 };
+        let len = cipher.decrypt(nonce, message, payload)?;
+        self.observer.message_read(message.len());
+        Ok(len)
+    }
+
+    /// Write a batch of messages, where each entry is the nonce, the payload and the buffer
+    /// the message is written to. A payload too large for one message can be sent this way
+    /// by splitting it into chunks of at most `max_message_len - TAGLEN` bytes under
+    /// consecutive nonces.
+    ///
+    /// Returns the result [`write_message`](Self::write_message) would give for each entry,
+    /// in order. With the `rayon` feature the entries are encrypted in parallel, which pays
+    /// off for large messages; the results are the same either way.
+    #[must_use]
+    pub fn write_message_batch(
+        &self,
+        batch: &mut [(u64, &[u8], &mut [u8])],
+    ) -> Vec<Result<usize, Error>> {
+        #[cfg(feature = "rayon")]
+        let entries = batch.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let entries = batch.iter_mut();
+        entries
+            .map(|(nonce, payload, message)| self.write_message(*nonce, payload, message))
+            .collect()
+    }
+
+    /// Read a batch of messages, e.g. the datagrams returned by one `recvmmsg()` call, where
+    /// each entry is the nonce, the message and the buffer its payload is written to.
+    ///
+    /// Returns the result [`read_message`](Self::read_message) would give for each entry, in
+    /// order, so one bad message doesn't affect the rest of the batch. To reject replays,
+    /// check each nonce with [`ReplayWindow::is_fresh()`] before the batch and
+    /// [`ReplayWindow::accept()`] the ones that were read successfully. With the `rayon`
+    /// feature the entries are decrypted in parallel.
+    #[must_use]
+    pub fn read_message_batch(
+        &self,
+        batch: &mut [(u64, &[u8], &mut [u8])],
+    ) -> Vec<Result<usize, Error>> {
+        #[cfg(feature = "rayon")]
+        let entries = batch.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let entries = batch.iter_mut();
+        entries
+            .map(|(nonce, message, payload)| self.read_message(*nonce, message, payload))
+            .collect()
+    }
+
+    /// Like [`read_message`](Self::read_message), but first rejects `nonce` if `window` has
+    /// already seen it, and records it in `window` once the message has been authenticated.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::Replayed` if `nonce` isn't fresh in `window`, and otherwise
+    /// in the same errors as [`read_message`](Self::read_message).
+    pub fn read_message_with_window(
+        &self,
+        nonce: u64,
+        window: &mut ReplayWindow,
+        message: &[u8],
+        payload: &mut [u8],
+    ) -> Result<usize, Error> {
+        if !window.is_fresh(nonce) {
+            return Err(StateProblem::Replayed.into());
+        }
+        let len = self.read_message(nonce, message, payload)?;
+        window.accept(nonce)?;
+        Ok(len)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is taken from
+    /// `message[..payload_len]` and encrypted in place.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` can't hold the payload and its tag, or if
+    /// the size of the output exceeds the max message length in the Noise Protocol (65535 bytes),
+    /// or the one set with `Builder::max_message_len()`.
+    pub fn write_message_in_place(
+        &self,
+        nonce: u64,
         message: &mut [u8],
+        payload_len: usize,
     ) -> Result<usize, Error> {
-        if payload.len() > MAXMSGLEN {
-            Err(Error::Input)
+        if !self.initiator && self.pattern.is_oneway() {
+            return Err(StateProblem::OneWay.into());
+        } else if payload_len + TAGLEN > self.max_message_len
+            || payload_len + TAGLEN > message.len()
+        {
+            return Err(Error::Input);
+        }
+
+        let cipher = if self.initiator { &self.cipherstates.0 } else { &self.cipherstates.1 };
+        let len = cipher.encrypt_ad_in_place(nonce, &[], message, payload_len)?;
+        self.observer.message_written(len);
+        Ok(len)
+    }
+
+    /// Like [`read_message`](Self::read_message), but the payload is decrypted in place
+    /// at the start of `message`.
+    ///
+    /// Returns the length of the payload in `message`.
+    ///
+    /// # Errors
+    /// Will result in `Error::Input` if the message is longer than the max message length
+    /// (65535 bytes, or the one set with `Builder::max_message_len()`).
+    ///
+    /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
+    /// authentication tag didn't verify.
+    ///
+    /// Will result in `StateProblem::Exhausted` if the max nonce overflows.
+    pub fn read_message_in_place(&self, nonce: u64, message: &mut [u8]) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
         } else if self.initiator && self.pattern.is_oneway() {
-            Err(StateProblem::OneWay.into())
-        } else {
-            let cipher = if self.initiator { &self.cipherstates.1 } else { &self.cipherstates.0 };
-            cipher.decrypt(nonce, payload, message)
+            return Err(StateProblem::OneWay.into());
         }
+        let len = message.len();
+        let cipher = if self.initiator { &self.cipherstates.1 } else { &self.cipherstates.0 };
+        let payload_len = cipher.decrypt_ad_in_place(nonce, &[], message)?;
+        self.observer.message_read(len);
+        Ok(payload_len)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is sent with its length
+    /// and padded up to a multiple of `block` bytes, to hide its exact size. A `block` larger
+    /// than any payload pads every message to the same size. Read it with
+    /// [`read_message_padded`](Self::read_message_padded).
+    ///
+    /// The padded payload is capped at the max message length, so payloads near that limit
+    /// are padded less.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `block` is zero, or if `message` can't hold the padded
+    /// payload or the payload and its length don't fit in the max message length.
+    pub fn write_message_padded(
+        &self,
+        nonce: u64,
+        payload: &[u8],
+        block: usize,
+        message: &mut [u8],
+    ) -> Result<usize, Error> {
+        let padded = crate::utils::pad(payload, block, self.max_message_len - TAGLEN, message)?;
+        self.write_message_in_place(nonce, message, padded)
+    }
+
+    /// Like [`read_message`](Self::read_message), but for messages written with
+    /// [`write_message_padded`](Self::write_message_padded): the length and padding are
+    /// stripped before the payload is returned.
+    ///
+    /// `payload` must be able to hold the padded payload, i.e. `message.len() - TAGLEN` bytes.
+    ///
+    /// Returns the number of bytes of payload at the start of `payload`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in the errors of [`read_message`](Self::read_message), and in
+    /// `Error::Input` if the decrypted payload isn't correctly framed.
+    pub fn read_message_padded(
+        &self,
+        nonce: u64,
+        message: &[u8],
+        payload: &mut [u8],
+    ) -> Result<usize, Error> {
+        let len = self.read_message(nonce, message, payload)?;
+        crate::utils::unpad(&mut payload[..len])
     }
 
     /// Generate a new key for the egress symmetric cipher according to Section 4.2
@@ -102,6 +455,7 @@ impl StatelessTransportState {
     /// responder is the responsibility of the application, as described in Section 11.3
     /// of the Noise Specification.
     pub fn rekey_outgoing(&mut self) {
+        self.observer.rekey();
         if self.initiator {
             self.cipherstates.rekey_initiator();
         } else {
@@ -114,6 +468,7 @@ impl StatelessTransportState {
     /// responder is the responsibility of the application, as described in Section 11.3
     /// of the Noise Specification.
     pub fn rekey_incoming(&mut self) {
+        self.observer.rekey();
         if self.initiator {
             self.cipherstates.rekey_responder();
         } else {
@@ -137,11 +492,13 @@ impl StatelessTransportState {
 
     /// Set a new key for the initiator-egress symmetric cipher.
     pub fn rekey_initiator_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        self.observer.rekey();
         self.cipherstates.rekey_initiator_manually(key);
     }
 
     /// Set a new key for the responder-egress symmetric cipher.
     pub fn rekey_responder_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        self.observer.rekey();
         self.cipherstates.rekey_responder_manually(key);
     }
 
@@ -1,15 +1,31 @@
 use crate::{
     cipherstate::CipherState,
-    constants::{CIPHERKEYLEN, MAXHASHLEN},
+    constants::{CIPHERKEYLEN, MAXHASHLEN, PSKLEN},
     error::Error,
     types::Hash,
 };
+use alloc::{boxed::Box, vec::Vec};
+use zeroize::{Zeroize, Zeroizing};
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub(crate) struct SymmetricStateData {
     h:       [u8; MAXHASHLEN],
     ck:      [u8; MAXHASHLEN],
     has_key: bool,
+    /// The key last given to the cipherstate, kept so a checkpoint can put it back.
+    k:       [u8; CIPHERKEYLEN],
+    /// The nonce of the cipherstate, only kept up to date in checkpoints.
+    n:       u64,
+}
+
+impl Zeroize for SymmetricStateData {
+    fn zeroize(&mut self) {
+        self.h.zeroize();
+        self.ck.zeroize();
+        self.has_key = false;
+        self.k.zeroize();
+        self.n = 0;
+    }
 }
 
 impl Default for SymmetricStateData {
@@ -18,19 +34,54 @@ impl Default for SymmetricStateData {
             h:       [0u8; MAXHASHLEN],
             ck:      [0u8; MAXHASHLEN],
             has_key: false,
+            k:       [0u8; CIPHERKEYLEN],
+            n:       0,
         }
     }
 }
 
 pub(crate) struct SymmetricState {
-    cipherstate: CipherState,
-    hasher:      Box<dyn Hash>,
-    inner:       SymmetricStateData,
+    cipherstate:      CipherState,
+    hasher:           Box<dyn Hash>,
+    inner:            Zeroizing<SymmetricStateData>,
+    /// Every operation so far, only kept for `Vector::record()`.
+    #[cfg(feature = "vectors")]
+    pub(crate) trace: Option<crate::vectors::SymmetricTrace>,
 }
 
 impl SymmetricState {
     pub fn new(cipherstate: CipherState, hasher: Box<dyn Hash>) -> SymmetricState {
-        SymmetricState { cipherstate, hasher, inner: SymmetricStateData::default() }
+        SymmetricState {
+            cipherstate,
+            hasher,
+            inner: Zeroizing::new(SymmetricStateData::default()),
+            #[cfg(feature = "vectors")]
+            trace: None,
+        }
+    }
+
+    /// Keep a trace of every operation from now on, see `Builder::trace_symmetric_state()`.
+    #[cfg(feature = "vectors")]
+    pub(crate) fn traced(mut self) -> Self {
+        self.trace = Some(crate::vectors::SymmetricTrace::default());
+        self
+    }
+
+    #[cfg(feature = "vectors")]
+    fn record(&mut self, operation: crate::vectors::SymmetricOperation) {
+        let hash_len = self.hasher.hash_len();
+        if let Some(trace) = &mut self.trace {
+            trace.steps.push(crate::vectors::SymmetricStep {
+                operation,
+                h: Zeroizing::new(self.inner.h[..hash_len].to_vec()),
+                ck: Zeroizing::new(self.inner.ck[..hash_len].to_vec()),
+            });
+        }
+    }
+
+    /// Give back the primitives this state was built with, e.g. to start a fresh handshake.
+    pub fn into_parts(self) -> (CipherState, Box<dyn Hash>) {
+        (self.cipherstate, self.hasher)
     }
 
     pub fn initialize(&mut self, handshake_name: &str) {
@@ -41,8 +92,10 @@ impl SymmetricState {
             self.hasher.input(handshake_name.as_bytes());
             self.hasher.result(&mut self.inner.h);
         }
-        copy_slices!(self.inner.h, &mut self.inner.ck);
+        self.inner.ck = self.inner.h;
         self.inner.has_key = false;
+        #[cfg(feature = "vectors")]
+        self.record(crate::vectors::SymmetricOperation::Initialize);
     }
 
     pub fn mix_key(&mut self, data: &[u8]) {
@@ -63,7 +116,13 @@ impl SymmetricState {
 
         self.inner.ck = hkdf_output.0;
         self.cipherstate.set(&cipher_key, 0);
+        self.inner.k = cipher_key;
         self.inner.has_key = true;
+        hkdf_output.0.zeroize();
+        hkdf_output.1.zeroize();
+        cipher_key.zeroize();
+        #[cfg(feature = "vectors")]
+        self.record(crate::vectors::SymmetricOperation::MixKey);
     }
 
     pub fn mix_hash(&mut self, data: &[u8]) {
@@ -72,6 +131,8 @@ impl SymmetricState {
         self.hasher.input(&self.inner.h[..hash_len]);
         self.hasher.input(data);
         self.hasher.result(&mut self.inner.h);
+        #[cfg(feature = "vectors")]
+        self.record(crate::vectors::SymmetricOperation::MixHash);
     }
 
     pub fn mix_key_and_hash(&mut self, data: &[u8]) {
@@ -92,6 +153,13 @@ impl SymmetricState {
         let mut cipher_key = [0u8; CIPHERKEYLEN];
         cipher_key.copy_from_slice(&hkdf_output.2[..CIPHERKEYLEN]);
         self.cipherstate.set(&cipher_key, 0);
+        self.inner.k = cipher_key;
+        hkdf_output.0.zeroize();
+        hkdf_output.1.zeroize();
+        hkdf_output.2.zeroize();
+        cipher_key.zeroize();
+        #[cfg(feature = "vectors")]
+        self.record(crate::vectors::SymmetricOperation::MixKeyAndHash);
     }
 
     pub fn has_key(&self) -> bool {
@@ -106,7 +174,7 @@ impl SymmetricState {
     ) -> Result<usize, Error> {
         let hash_len = self.hasher.hash_len();
         let output_len = if self.inner.has_key {
-            self.cipherstate.encrypt_ad(&self.inner.h[..hash_len], plaintext, out)?
+            self.cipherstate.encrypt_with_ad(&self.inner.h[..hash_len], plaintext, out)?
         } else {
             copy_slices!(plaintext, out);
             plaintext.len()
@@ -118,10 +186,10 @@ impl SymmetricState {
     pub fn decrypt_and_mix_hash(&mut self, data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
         let hash_len = self.hasher.hash_len();
         let payload_len = if self.inner.has_key {
-            self.cipherstate.decrypt_ad(&self.inner.h[..hash_len], data, out)?
+            self.cipherstate.decrypt_with_ad(&self.inner.h[..hash_len], data, out)?
         } else {
             if out.len() < data.len() {
-                return Err(Error::Decrypt);
+                return Err(Error::Decrypt { token: None });
             }
             copy_slices!(data, out);
             data.len()
@@ -130,6 +198,39 @@ impl SymmetricState {
         Ok(payload_len)
     }
 
+    /// Encrypt the payload at the start of `in_out` in place and mix in the hash of the output
+    pub fn encrypt_and_mix_hash_in_place(
+        &mut self,
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> Result<usize, Error> {
+        let hash_len = self.hasher.hash_len();
+        let output_len = if self.inner.has_key {
+            self.cipherstate.encrypt_ad_in_place(
+                &self.inner.h[..hash_len],
+                in_out,
+                plaintext_len,
+            )?
+        } else {
+            plaintext_len
+        };
+        self.mix_hash(&in_out[..output_len]);
+        Ok(output_len)
+    }
+
+    /// Mix in the hash of `in_out` and decrypt it in place. On failure the hash has already
+    /// been mixed, so callers must restore a checkpoint.
+    pub fn decrypt_and_mix_hash_in_place(&mut self, in_out: &mut [u8]) -> Result<usize, Error> {
+        let hash_len = self.hasher.hash_len();
+        let h = Zeroizing::new(self.inner.h);
+        self.mix_hash(in_out);
+        if self.inner.has_key {
+            self.cipherstate.decrypt_ad_in_place(&h[..hash_len], in_out)
+        } else {
+            Ok(in_out.len())
+        }
+    }
+
     pub fn split(&mut self, child1: &mut CipherState, child2: &mut CipherState) {
         let mut hkdf_output = ([0u8; MAXHASHLEN], [0u8; MAXHASHLEN]);
         self.split_raw(&mut hkdf_output.0, &mut hkdf_output.1);
@@ -140,6 +241,14 @@ impl SymmetricState {
         cipher_keys.1.copy_from_slice(&hkdf_output.1[..CIPHERKEYLEN]);
         child1.set(&cipher_keys.0, 0);
         child2.set(&cipher_keys.1, 0);
+        #[cfg(feature = "vectors")]
+        if let Some(trace) = &mut self.trace {
+            trace.split = Some(Zeroizing::new([cipher_keys.0, cipher_keys.1]));
+        }
+        hkdf_output.0.zeroize();
+        hkdf_output.1.zeroize();
+        cipher_keys.0.zeroize();
+        cipher_keys.1.zeroize();
     }
 
     pub fn split_raw(&mut self, out1: &mut [u8], out2: &mut [u8]) {
@@ -147,12 +256,35 @@ impl SymmetricState {
         self.hasher.hkdf(&self.inner.ck[..hash_len], &[0u8; 0], 2, out1, out2, &mut []);
     }
 
-    pub(crate) fn checkpoint(&mut self) -> SymmetricStateData {
-        self.inner
+    /// Derive the secret behind `export_keying_material()` from the final chaining key and
+    /// handshake hash, consuming the state so the chaining key isn't kept around.
+    pub fn into_exporter(mut self) -> Exporter {
+        let hash_len = self.hasher.hash_len();
+        let mut secret = Zeroizing::new([0u8; MAXHASHLEN]);
+        self.hasher.hkdf(
+            &self.inner.ck[..hash_len],
+            &self.inner.h[..hash_len],
+            1,
+            &mut *secret,
+            &mut [],
+            &mut [],
+        );
+        Exporter { hasher: self.hasher, secret }
+    }
+
+    /// Save the state, including the key and nonce of the cipherstate, so that a message that
+    /// fails partway through can be undone with `restore()`.
+    pub(crate) fn checkpoint(&mut self) -> Zeroizing<SymmetricStateData> {
+        let mut checkpoint = self.inner.clone();
+        checkpoint.n = self.cipherstate.nonce();
+        checkpoint
     }
 
-    pub(crate) fn restore(&mut self, checkpoint: SymmetricStateData) {
+    pub(crate) fn restore(&mut self, checkpoint: Zeroizing<SymmetricStateData>) {
         self.inner = checkpoint;
+        if self.inner.has_key {
+            self.cipherstate.set(&self.inner.k, self.inner.n);
+        }
     }
 
     pub fn handshake_hash(&self) -> &[u8] {
@@ -160,3 +292,38 @@ impl SymmetricState {
         &self.inner.h[..hash_len]
     }
 }
+
+/// Derives keying material bound to a finished session, see
+/// `TransportState::export_keying_material()`.
+pub(crate) struct Exporter {
+    hasher: Box<dyn Hash>,
+    secret: Zeroizing<[u8; MAXHASHLEN]>,
+}
+
+impl Exporter {
+    /// Fill `out` with `HKDF(secret, len(label) || label || context)`, where `len(label)` is
+    /// a 2-byte big-endian length so that no two (label, context) pairs collide.
+    pub fn export(&mut self, label: &[u8], context: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        let hash_len = self.hasher.hash_len();
+        let label_len = u16::try_from(label.len()).map_err(|_| Error::Input)?;
+        if out.len() > hash_len {
+            return Err(Error::Input);
+        }
+
+        let mut info = Zeroizing::new(Vec::with_capacity(2 + label.len() + context.len()));
+        info.extend_from_slice(&label_len.to_be_bytes());
+        info.extend_from_slice(label);
+        info.extend_from_slice(context);
+        let mut output = Zeroizing::new([0u8; MAXHASHLEN]);
+        self.hasher.hkdf(&self.secret[..hash_len], &info, 1, &mut *output, &mut [], &mut []);
+        out.copy_from_slice(&output[..out.len()]);
+        Ok(())
+    }
+
+    /// Derive the PSK that resumes this session, see `TransportState::resumption_secret()`.
+    pub fn resumption_secret(&mut self, context: &[u8]) -> Result<Zeroizing<[u8; PSKLEN]>, Error> {
+        let mut secret = Zeroizing::new([0u8; PSKLEN]);
+        self.export(b"snow resumption", context, &mut *secret)?;
+        Ok(secret)
+    }
+}
@@ -0,0 +1,431 @@
+//! Blocking adapters that run a Noise session over std I/O: [`NoiseStream`] over any
+//! `Read + Write` stream, and [`NoiseDatagram`] over a connected UDP socket.
+//!
+//! On a stream, every Noise message is framed with a 2-byte big-endian length prefix, as in
+//! the examples of the Noise Specification.
+//!
+//! This is also the way to send data larger than the 65535-byte Noise message limit:
+//! `write_all()` splits it into messages of the maximum size, and the reading side gets the
+//! bytes back as one continuous stream, e.g. with `read_exact()` or `read_to_end()`.
+
+use crate::{
+    constants::{MAXMSGLEN, TAGLEN},
+    error::{Error, StateProblem},
+    handshakestate::HandshakeState,
+    stateless_transportstate::{ReplayWindow, StatelessTransportState},
+    transportstate::TransportState,
+};
+use std::{
+    io::{self, Read, Write},
+    net::UdpSocket,
+    time::Duration,
+};
+
+/// A stream that encrypts everything written to it and decrypts everything read from it.
+///
+/// Created by [`NoiseStream::handshake()`], which drives the handshake to completion over the
+/// inner stream before any application data is exchanged. Writes are sent as one transport
+/// message per call, capped at the session's maximum message length, so wrap the stream in a
+/// `BufWriter` to avoid sending many small messages.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{sync_io::NoiseStream, Builder};
+/// # use std::{io::{Read, Write}, net::{TcpListener, TcpStream}, thread};
+/// #
+/// # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+/// # fn try_main() -> Result<(), Box<dyn std::error::Error>> {
+/// let params: snow::params::NoiseParams = "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?;
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// let addr = listener.local_addr()?;
+///
+/// let responder = Builder::new(params.clone()).build_responder()?;
+/// let server = thread::spawn(move || -> std::io::Result<Vec<u8>> {
+///     let (socket, _) = listener.accept()?;
+///     let mut stream = NoiseStream::handshake(socket, responder)?;
+///     let mut received = Vec::new();
+///     stream.read_to_end(&mut received)?;
+///     Ok(received)
+/// });
+///
+/// let initiator = Builder::new(params).build_initiator()?;
+/// let mut stream = NoiseStream::handshake(TcpStream::connect(addr)?, initiator)?;
+/// stream.write_all(b"hello")?;
+/// drop(stream);
+///
+/// assert_eq!(server.join().unwrap()?, b"hello");
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(any(feature = "default-resolver", feature = "ring-accelerated")))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+pub struct NoiseStream<T> {
+    inner:     T,
+    transport: TransportState,
+    message:   Box<[u8]>,
+    plaintext: Box<[u8]>,
+    pos:       usize,
+    len:       usize,
+}
+
+impl<T: Read + Write> NoiseStream<T> {
+    /// Run the handshake in `handshake` over `inner`, then wrap it in transport mode.
+    ///
+    /// The handshake is sent with empty payloads. Whether this side is the initiator or the
+    /// responder is decided by how `handshake` was built.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error of the inner stream, or with `io::ErrorKind::InvalidData`
+    /// wrapping a [`crate::Error`] if a handshake message can't be processed.
+    pub fn handshake(mut inner: T, mut handshake: HandshakeState) -> io::Result<Self> {
+        let mut message = vec![0u8; MAXMSGLEN].into_boxed_slice();
+        let mut plaintext = vec![0u8; MAXMSGLEN].into_boxed_slice();
+
+        while !handshake.is_handshake_finished() {
+            if handshake.is_my_turn() {
+                let len = handshake.write_message(&[], &mut message).map_err(invalid_data)?;
+                write_frame(&mut inner, &message[..len])?;
+            } else {
+                let len = read_frame(&mut inner, &mut message)?
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                handshake.read_message(&message[..len], &mut plaintext).map_err(invalid_data)?;
+            }
+        }
+
+        let transport = handshake.into_transport_mode().map_err(invalid_data)?;
+        Ok(Self { inner, transport, message, plaintext, pos: 0, len: 0 })
+    }
+
+    /// Get a reference to the inner stream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner stream.
+    ///
+    /// Reading from or writing to it directly will corrupt the framing of the session.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Get a reference to the transport state of the session.
+    pub fn transport(&self) -> &TransportState {
+        &self.transport
+    }
+
+    /// Get a mutable reference to the transport state of the session, e.g. to rekey it.
+    pub fn transport_mut(&mut self) -> &mut TransportState {
+        &mut self.transport
+    }
+
+    /// Unwrap the stream, returning the inner stream and the transport state.
+    ///
+    /// Any decrypted data that hasn't been read yet is discarded.
+    pub fn into_inner(self) -> (T, TransportState) {
+        (self.inner, self.transport)
+    }
+}
+
+impl<T: Read + Write> Read for NoiseStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.len {
+            let len = match read_frame(&mut self.inner, &mut self.message)? {
+                Some(len) => len,
+                None => return Ok(0),
+            };
+            self.len = self
+                .transport
+                .read_message(&self.message[..len], &mut self.plaintext)
+                .map_err(invalid_data)?;
+            self.pos = 0;
+        }
+
+        let n = buf.len().min(self.len - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<T: Read + Write> Write for NoiseStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.transport.max_message_len() - TAGLEN);
+        let len =
+            self.transport.write_message(&buf[..n], &mut self.message).map_err(invalid_data)?;
+        write_frame(&mut self.inner, &self.message[..len])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The length of the explicit nonce in front of every transport datagram.
+const NONCELEN: usize = 8;
+
+/// A connected UDP socket that seals every datagram sent and opens every datagram received.
+///
+/// Created by [`NoiseDatagram::handshake()`], which drives the handshake over the socket,
+/// retransmitting this side's last handshake message whenever the peer's next one doesn't
+/// arrive in time. Datagrams may be lost, duplicated or reordered, so the transport uses a
+/// [`StatelessTransportState`]: every datagram carries its nonce as an 8-byte big-endian
+/// prefix, and a [`ReplayWindow`] drops the ones received before.
+///
+/// The socket must be [connected](UdpSocket::connect) to the peer, so that only its datagrams
+/// are received.
+///
+/// # Examples
+///
+/// ```
+/// # use snow::{sync_io::NoiseDatagram, Builder};
+/// # use std::{net::UdpSocket, thread, time::Duration};
+/// #
+/// # #[cfg(any(feature = "default-resolver", feature = "ring-accelerated"))]
+/// # fn try_main() -> Result<(), Box<dyn std::error::Error>> {
+/// let params: snow::params::NoiseParams = "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse()?;
+/// let (client, server) = (UdpSocket::bind("127.0.0.1:0")?, UdpSocket::bind("127.0.0.1:0")?);
+/// client.connect(server.local_addr()?)?;
+/// server.connect(client.local_addr()?)?;
+///
+/// let responder = Builder::new(params.clone()).build_responder()?;
+/// let server = thread::spawn(move || -> std::io::Result<Vec<u8>> {
+///     let mut server = NoiseDatagram::handshake(server, responder, Duration::from_secs(1), 5)?;
+///     let mut payload = vec![0u8; 1024];
+///     let len = server.recv(&mut payload)?;
+///     Ok(payload[..len].to_vec())
+/// });
+///
+/// let initiator = Builder::new(params).build_initiator()?;
+/// let mut client = NoiseDatagram::handshake(client, initiator, Duration::from_secs(1), 5)?;
+/// client.send(b"hello")?;
+///
+/// assert_eq!(server.join().unwrap()?, b"hello");
+/// #     Ok(())
+/// # }
+/// #
+/// # #[cfg(not(any(feature = "default-resolver", feature = "ring-accelerated")))]
+/// # fn try_main() -> Result<(), ()> { Ok(()) }
+/// #
+/// # fn main() {
+/// #     try_main().unwrap();
+/// # }
+/// ```
+pub struct NoiseDatagram {
+    socket:      UdpSocket,
+    transport:   StatelessTransportState,
+    window:      ReplayWindow,
+    nonce:       u64,
+    message:     Box<[u8]>,
+    /// The peer's last handshake message and our reply to it, if our reply finished the
+    /// handshake, until the peer shows it has received that reply.
+    last_flight: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl NoiseDatagram {
+    /// Run the handshake in `handshake` over `socket`, then wrap it in transport mode.
+    ///
+    /// The handshake is sent with empty payloads. Whenever the peer's next message doesn't
+    /// arrive within `timeout`, this side's last message is sent again, up to
+    /// `max_retransmits` times in a row; while nothing has been sent yet, as for a responder
+    /// waiting for the first message, the timeouts count all the same. A repeated copy of the peer's last message means our
+    /// reply was lost, so the reply is sent again; this also works after the handshake has
+    /// finished, from [`recv()`](Self::recv). Datagrams that aren't a valid handshake message
+    /// are dropped.
+    ///
+    /// The socket's read timeout is set to `timeout` during the handshake and restored after.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error of the socket, with `io::ErrorKind::TimedOut` if the peer's next
+    /// message still hasn't arrived after `max_retransmits` retransmissions, or with
+    /// `io::ErrorKind::InvalidData` wrapping a [`crate::Error`] if a handshake message can't
+    /// be written.
+    pub fn handshake(
+        socket: UdpSocket,
+        mut handshake: HandshakeState,
+        timeout: Duration,
+        max_retransmits: u32,
+    ) -> io::Result<Self> {
+        let read_timeout = socket.read_timeout()?;
+        socket.set_read_timeout(Some(timeout))?;
+        let last_flight = drive_handshake(&socket, &mut handshake, max_retransmits);
+        socket.set_read_timeout(read_timeout)?;
+        let last_flight = last_flight?;
+
+        let transport = handshake.into_stateless_transport_mode().map_err(invalid_data)?;
+        Ok(Self {
+            socket,
+            transport,
+            window: ReplayWindow::new(),
+            nonce: 0,
+            message: vec![0u8; NONCELEN + MAXMSGLEN].into_boxed_slice(),
+            last_flight,
+        })
+    }
+
+    /// Seal `payload` into one datagram and send it to the peer.
+    ///
+    /// Returns the number of payload bytes sent, which is always all of them.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error of the socket (e.g. if the datagram is larger than UDP allows),
+    /// or with `io::ErrorKind::InvalidData` wrapping a [`crate::Error`] if `payload` doesn't
+    /// fit in a Noise message or the nonces are exhausted.
+    pub fn send(&mut self, payload: &[u8]) -> io::Result<usize> {
+        // The replay window never accepts `u64::MAX`, so the peer couldn't read it.
+        if self.nonce == u64::MAX {
+            return Err(invalid_data(StateProblem::Exhausted.into()));
+        }
+        let (nonce, message) = self.message.split_at_mut(NONCELEN);
+        nonce.copy_from_slice(&self.nonce.to_be_bytes());
+        let len =
+            self.transport.write_message(self.nonce, payload, message).map_err(invalid_data)?;
+        self.socket.send(&self.message[..NONCELEN + len])?;
+        self.nonce += 1;
+        Ok(payload.len())
+    }
+
+    /// Receive the next datagram from the peer that opens successfully, and write its payload
+    /// to `payload`.
+    ///
+    /// Returns the number of bytes written to `payload`. Datagrams that don't open (forged,
+    /// corrupted or truncated ones, and those received before) are dropped while waiting, so
+    /// this blocks until a valid one arrives or the socket's read timeout expires.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the error of the socket, or with `io::ErrorKind::InvalidData` wrapping a
+    /// [`crate::Error`] if a valid datagram can't be opened into `payload`, e.g. because it's
+    /// too small.
+    pub fn recv(&mut self, payload: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let len = self.socket.recv(&mut self.message)?;
+            if let Some((received, sent)) = &self.last_flight {
+                if self.message[..len] == received[..] {
+                    self.socket.send(sent)?;
+                    continue;
+                }
+            }
+            if len < NONCELEN {
+                continue;
+            }
+
+            let (nonce, message) = self.message[..len].split_at(NONCELEN);
+            let nonce = u64::from_be_bytes(nonce.try_into().expect("nonce is 8 bytes"));
+            match self.transport.read_message_with_window(nonce, &mut self.window, message, payload)
+            {
+                Ok(len) => {
+                    self.last_flight = None;
+                    return Ok(len);
+                },
+                Err(Error::Decrypt { .. } | Error::State(StateProblem::Replayed)) => {},
+                Err(e) => return Err(invalid_data(e)),
+            }
+        }
+    }
+
+    /// Get a reference to the socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Get a reference to the transport state of the session.
+    pub fn transport(&self) -> &StatelessTransportState {
+        &self.transport
+    }
+
+    /// Unwrap the socket, returning it and the transport state.
+    ///
+    /// The nonces of the session are managed by this wrapper, so continuing the session from
+    /// the transport state means tracking them from where it left off.
+    pub fn into_inner(self) -> (UdpSocket, StatelessTransportState) {
+        (self.socket, self.transport)
+    }
+}
+
+/// Run the handshake over `socket`, returning the peer's last message and our reply to it
+/// if that reply was the last message of the handshake.
+fn drive_handshake(
+    socket: &UdpSocket,
+    handshake: &mut HandshakeState,
+    max_retransmits: u32,
+) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut message = vec![0u8; MAXMSGLEN].into_boxed_slice();
+    let mut payload = vec![0u8; MAXMSGLEN].into_boxed_slice();
+    let (mut sent, mut received) = (Vec::new(), Vec::new());
+    let mut retransmits = 0;
+
+    while !handshake.is_handshake_finished() {
+        if handshake.is_my_turn() {
+            let len = handshake.write_message(&[], &mut message).map_err(invalid_data)?;
+            sent = message[..len].to_vec();
+            socket.send(&sent)?;
+            retransmits = 0;
+            continue;
+        }
+
+        let len = match socket.recv(&mut message) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                if retransmits == max_retransmits {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                retransmits += 1;
+                if !sent.is_empty() {
+                    socket.send(&sent)?;
+                }
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+        if !received.is_empty() && message[..len] == received[..] {
+            // The peer sent its last message again, so it didn't get our reply.
+            socket.send(&sent)?;
+        } else if handshake.read_message(&message[..len], &mut payload).is_ok() {
+            received = message[..len].to_vec();
+            sent.clear();
+        }
+    }
+
+    Ok((!sent.is_empty() && !received.is_empty()).then_some((received, sent)))
+}
+
+fn invalid_data(e: crate::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn write_frame(inner: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    // Noise messages never exceed 65535 bytes, so the length always fits.
+    inner.write_all(&(message.len() as u16).to_be_bytes())?;
+    inner.write_all(message)
+}
+
+/// Read one frame into `message`, returning `None` if the stream ended cleanly before it.
+fn read_frame(inner: &mut impl Read, message: &mut [u8]) -> io::Result<Option<usize>> {
+    let mut prefix = [0u8; 2];
+    let mut filled = 0;
+    while filled < prefix.len() {
+        match inner.read(&mut prefix[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    let len = usize::from(u16::from_be_bytes(prefix));
+    inner.read_exact(&mut message[..len])?;
+    Ok(Some(len))
+}
@@ -1,12 +1,68 @@
 use crate::{
-    cipherstate::CipherStates,
-    constants::{CIPHERKEYLEN, MAXDHLEN, MAXMSGLEN, TAGLEN},
+    cipherstate::{CipherState, CipherStates},
+    constants::{CIPHERKEYLEN, MAXDHLEN, MAXHASHLEN, PSKLEN, TAGLEN},
     error::{Error, StateProblem},
     handshakestate::HandshakeState,
+    observer::Observer,
     params::HandshakePattern,
+    symmetricstate::Exporter,
     utils::Toggle,
 };
-use std::{convert::TryFrom, fmt};
+use core::{convert::TryFrom, fmt};
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+#[cfg(all(feature = "std", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// The associated data of the message written by `TransportState::write_close()`, which sets
+/// it apart from regular messages (which have none).
+const CLOSE_AD: &[u8] = b"Noise close";
+
+/// A policy for automatically rekeying the transport ciphers, see Section 11.3 of the
+/// Noise Specification.
+///
+/// Each direction is tracked separately: the sender rekeys its outgoing cipher once the
+/// threshold is crossed, and the receiver rekeys its incoming cipher at the same point, so
+/// both peers must be configured with the same policy.
+///
+/// Rekeying is one-way, so this doubles as a symmetric ratchet: once a direction has rekeyed,
+/// compromising its current key doesn't expose the messages sent before. `AfterMessages(1)`
+/// ratchets after every message, at the cost of one extra cipher operation each, and requires
+/// messages to be delivered in order.
+///
+/// A half-duplex [`TransportState`] applies the policy to its one shared cipher, counting sent
+/// and received messages separately, which stays in step on both peers. A
+/// [`StatelessTransportState`](crate::StatelessTransportState) ignores the policy, since it has
+/// no message ordering to count against; call its `rekey_outgoing()` and `rekey_incoming()`
+/// instead.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RekeyPolicy {
+    /// Rekey a direction after this many messages have been sent (or received) in it.
+    AfterMessages(u64),
+    /// Rekey a direction once this many bytes of ciphertext have been sent (or received)
+    /// in it.
+    AfterBytes(u64),
+}
+
+impl RekeyPolicy {
+    pub(crate) fn is_valid(self) -> bool {
+        match self {
+            RekeyPolicy::AfterMessages(n) | RekeyPolicy::AfterBytes(n) => n > 0,
+        }
+    }
+
+    /// Advance `counter` by one message of `len` bytes, returning whether a rekey is due.
+    fn advance(self, counter: &mut u64, len: usize) -> bool {
+        let (step, threshold) = match self {
+            RekeyPolicy::AfterMessages(n) => (1, n),
+            RekeyPolicy::AfterBytes(n) => (len as u64, n),
+        };
+        *counter = counter.saturating_add(step);
+        *counter >= threshold
+    }
+}
 
 /// A state machine encompassing the transport phase of a Noise session, using the two
 /// `CipherState`s (for sending and receiving) that were spawned from the `SymmetricState`'s
@@ -14,11 +70,46 @@ use std::{convert::TryFrom, fmt};
 ///
 /// Also see: [the relevant Noise spec section](https://noiseprotocol.org/noise.html#the-handshakestate-object).
 pub struct TransportState {
-    cipherstates: CipherStates,
-    pattern:      HandshakePattern,
-    dh_len:       usize,
-    rs:           Toggle<[u8; MAXDHLEN]>,
-    initiator:    bool,
+    cipherstates:    CipherStates,
+    pattern:         HandshakePattern,
+    dh_len:          usize,
+    rs:              Toggle<[u8; MAXDHLEN]>,
+    initiator:       bool,
+    h:               Zeroizing<[u8; MAXHASHLEN]>,
+    h_len:           usize,
+    exporter:        Exporter,
+    rekey_policy:    Option<RekeyPolicy>,
+    max_message_len: usize,
+    sent:            u64,
+    received:        u64,
+    /// In half-duplex mode, whether it's this side's turn to send.
+    half_duplex:     Option<bool>,
+    send_closed:     bool,
+    recv_closed:     bool,
+    stats:           TransportStats,
+    observer:        Observer,
+    #[cfg(all(feature = "std", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+    established:     Instant,
+}
+
+/// Counters of the traffic of a [`TransportState`], see [`TransportState::stats()`].
+///
+/// Bytes are counted as ciphertext, i.e. including the authentication tags. The nonces the
+/// next messages will use are available from [`TransportState::sending_nonce()`] and
+/// [`TransportState::receiving_nonce()`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TransportStats {
+    /// The number of messages written.
+    pub messages_sent:     u64,
+    /// The number of bytes of the messages written.
+    pub bytes_sent:        u64,
+    /// The number of messages read successfully.
+    pub messages_received: u64,
+    /// The number of bytes of the messages read successfully.
+    pub bytes_received:    u64,
+    /// The number of rekeys performed in either direction, automatic or manual.
+    pub rekeys:            u64,
 }
 
 impl TransportState {
@@ -28,10 +119,125 @@ impl TransportState {
         }
 
         let dh_len = handshake.dh_len();
-        let HandshakeState { cipherstates, params, rs, initiator, .. } = handshake;
+        let (h, h_len) = handshake.handshake_hash_buf();
+        let HandshakeState {
+            cipherstates,
+            params,
+            rs,
+            initiator,
+            symmetricstate,
+            rekey_policy,
+            max_message_len,
+            observer,
+            ..
+        } = handshake;
         let pattern = params.handshake.pattern;
+        observer.transport_ready(&h[..h_len]);
+
+        Ok(TransportState {
+            cipherstates,
+            pattern,
+            dh_len,
+            rs,
+            initiator,
+            h,
+            h_len,
+            exporter: symmetricstate.into_exporter(),
+            rekey_policy,
+            max_message_len,
+            sent: 0,
+            received: 0,
+            half_duplex: None,
+            send_closed: false,
+            recv_closed: false,
+            stats: TransportStats::default(),
+            observer,
+            #[cfg(all(feature = "std", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+            established: Instant::now(),
+        })
+    }
+
+    pub(crate) fn new_half_duplex(handshake: HandshakeState) -> Result<Self, Error> {
+        if handshake.params.handshake.pattern.is_oneway() {
+            return Err(StateProblem::OneWay.into());
+        }
+        let mut transport = Self::new(handshake)?;
+        transport.half_duplex = Some(transport.initiator);
+        Ok(transport)
+    }
+
+    /// Whether outgoing messages use the initiator-egress cipher.
+    fn sends_as_initiator(&self) -> bool {
+        self.initiator || self.half_duplex.is_some()
+    }
+
+    /// Whether incoming messages use the initiator-egress cipher.
+    fn receives_as_initiator(&self) -> bool {
+        !self.initiator || self.half_duplex.is_some()
+    }
+
+    fn check_write(&self) -> Result<(), Error> {
+        if !self.initiator && self.pattern.is_oneway() {
+            Err(StateProblem::OneWay.into())
+        } else if self.send_closed {
+            Err(StateProblem::Closed.into())
+        } else if self.half_duplex == Some(false) {
+            Err(StateProblem::NotTurnToWrite.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_read(&self) -> Result<(), Error> {
+        if self.initiator && self.pattern.is_oneway() {
+            Err(StateProblem::OneWay.into())
+        } else if self.recv_closed {
+            Err(StateProblem::Closed.into())
+        } else if self.half_duplex == Some(true) {
+            Err(StateProblem::NotTurnToRead.into())
+        } else {
+            Ok(())
+        }
+    }
 
-        Ok(TransportState { cipherstates, pattern, dh_len, rs, initiator })
+    fn finish_write(&mut self, len: usize) {
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += len as u64;
+        self.observer.message_written(len);
+        if let Some(policy) = self.rekey_policy {
+            if policy.advance(&mut self.sent, len) {
+                self.rekey_outgoing();
+            }
+        }
+        if let Some(my_turn) = &mut self.half_duplex {
+            *my_turn = false;
+        }
+    }
+
+    fn finish_read(&mut self, len: usize) {
+        self.stats.messages_received += 1;
+        self.stats.bytes_received += len as u64;
+        self.observer.message_read(len);
+        if let Some(policy) = self.rekey_policy {
+            if policy.advance(&mut self.received, len) {
+                self.rekey_incoming();
+            }
+        }
+        if let Some(my_turn) = &mut self.half_duplex {
+            *my_turn = true;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+
+    /// The number of bytes every transport message adds on top of its payload, i.e. the size
+    /// of the authentication tag.
+    #[must_use]
+    pub const fn message_overhead(&self) -> usize {
+        TAGLEN
     }
 
     /// Get the remote party's static public key, if available.
@@ -45,6 +251,56 @@ impl TransportState {
         self.rs.get().map(|rs| &rs[..self.dh_len])
     }
 
+    /// Get the final handshake hash, e.g. for channel binding.
+    ///
+    /// Returns a slice of length `Hasher.hash_len()` (i.e. HASHLEN for the chosen Hash function).
+    ///
+    /// See: <https://noiseprotocol.org/noise.html#channel-binding>
+    #[must_use]
+    pub fn get_handshake_hash(&self) -> &[u8] {
+        &self.h[..self.h_len]
+    }
+
+    /// Derive `out.len()` bytes of keying material bound to this session, e.g. to key a
+    /// subsystem outside of Noise, in the style of a TLS exporter.
+    ///
+    /// The output is `HKDF(exporter_secret, len(label) || label || context)`, truncated to
+    /// `out.len()`, where `exporter_secret = HKDF(ck, h)` is taken from the final chaining key
+    /// and handshake hash, and `len(label)` is 2 bytes big-endian. Both peers derive the same
+    /// material for the same `label` and `context`, and it reveals nothing about the transport
+    /// keys. Use a distinct `label` for each purpose.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `out` is longer than the hash length of the session
+    /// or `label` is longer than 65535 bytes.
+    pub fn export_keying_material(
+        &mut self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        self.exporter.export(label, context, out)
+    }
+
+    /// Derive a secret that lets the peers resume this session later with a cheaper
+    /// handshake, like a TLS session ticket: pass it to
+    /// [`Builder::resumption_psk()`](crate::Builder::resumption_psk) of a `psk` pattern such as
+    /// `NNpsk0`, and only someone who took part in this session can complete that handshake.
+    /// `context` binds the secret to e.g. a ticket identifier, and must match on both sides.
+    ///
+    /// The secret is an output of [`export_keying_material()`](Self::export_keying_material)
+    /// under its own label. Use each secret for only one resumption: the first message of a
+    /// `psk0` handshake can be replayed, so drop a secret once it's been used.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if the session's hash is shorter than a PSK, which is
+    /// never the case for the hashes in the Noise specification.
+    pub fn resumption_secret(&mut self, context: &[u8]) -> Result<Zeroizing<[u8; PSKLEN]>, Error> {
+        self.exporter.resumption_secret(context)
+    }
+
     /// Construct a message from `payload` (and pending handshake tokens if in handshake state),
     /// and write it to the `message` buffer.
     ///
@@ -53,48 +309,250 @@ impl TransportState {
     /// # Errors
     ///
     /// Will result in `Error::Input` if the size of the output exceeds the max message
-    /// length in the Noise Protocol (65535 bytes).
+    /// length in the Noise Protocol (65535 bytes), or the one set with
+    /// `Builder::max_message_len()`.
     pub fn write_message(&mut self, payload: &[u8], message: &mut [u8]) -> Result<usize, Error> {
-        if !self.initiator && self.pattern.is_oneway() {
-            return Err(StateProblem::OneWay.into());
-        } else if payload.len() + TAGLEN > MAXMSGLEN || payload.len() + TAGLEN > message.len() {
+        self.check_write()?;
+        if payload.len() + TAGLEN > self.max_message_len || payload.len() + TAGLEN > message.len() {
             return Err(Error::Input);
         }
 
-        let cipher =
-            if self.initiator { &mut self.cipherstates.0 } else { &mut self.cipherstates.1 };
-        cipher.encrypt(payload, message)
+        let cipher = if self.sends_as_initiator() {
+            &mut self.cipherstates.0
+        } else {
+            &mut self.cipherstates.1
+        };
+        let len = cipher.encrypt(payload, message)?;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is the concatenation of
+    /// the `payload` fragments, which are encrypted as a single Noise message without first
+    /// being joined into a separate buffer.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` can't hold the payload and the rest of the
+    /// message, or if the size of the output exceeds the max message length in the Noise
+    /// Protocol (65535 bytes), or the one set with `Builder::max_message_len()`.
+    #[cfg(feature = "std")]
+    pub fn write_message_vectored(
+        &mut self,
+        payload: &[IoSlice<'_>],
+        message: &mut [u8],
+    ) -> Result<usize, Error> {
+        let payload_len = crate::utils::gather(payload, message)?;
+        self.write_message_in_place(message, payload_len)
     }
 
     /// Read a noise message from `message` and write the payload to the `payload` buffer.
     ///
     /// Returns the number of bytes written to `payload`.
     ///
+    /// A failed read leaves the session as it was before the call: the nonce, the rekey policy
+    /// count and the stats only advance for a message that authenticates. A garbled or forged
+    /// message can be dropped and the session continued with the next one received.
+    ///
     /// # Errors
-    /// Will result in `Error::Input` if the message is more than 65535 bytes.
+    /// Will result in `Error::Input` if the message is longer than the max message length
+    /// (65535 bytes, or the one set with `Builder::max_message_len()`).
     ///
     /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
     /// authentication tag didn't verify.
     ///
     /// Will result in `StateProblem::Exhausted` if the max nonce overflows.
+    ///
+    /// Will result in `StateProblem::Closed` if the message was written with
+    /// [`write_close`](Self::write_close), and for every read after that.
     pub fn read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
-        if message.len() > MAXMSGLEN {
-            Err(Error::Input)
-        } else if self.initiator && self.pattern.is_oneway() {
-            Err(StateProblem::OneWay.into())
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
+        }
+        self.check_read()?;
+
+        let cipher = if self.receives_as_initiator() {
+            &mut self.cipherstates.0
         } else {
-            let cipher =
-                if self.initiator { &mut self.cipherstates.1 } else { &mut self.cipherstates.0 };
-            cipher.decrypt(message, payload)
+            &mut self.cipherstates.1
+        };
+        let len = match cipher.decrypt(message, payload) {
+            Err(Error::Decrypt { token: None }) if message.len() == TAGLEN => {
+                return self.read_close(message)
+            },
+            res => res?,
+        };
+        self.finish_read(message.len());
+        Ok(len)
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is taken from
+    /// `message[..payload_len]` and encrypted in place.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` can't hold the payload and its tag, or if
+    /// the size of the output exceeds the max message length in the Noise Protocol (65535 bytes),
+    /// or the one set with `Builder::max_message_len()`.
+    pub fn write_message_in_place(
+        &mut self,
+        message: &mut [u8],
+        payload_len: usize,
+    ) -> Result<usize, Error> {
+        self.check_write()?;
+        if payload_len + TAGLEN > self.max_message_len || payload_len + TAGLEN > message.len() {
+            return Err(Error::Input);
         }
+
+        let cipher = if self.sends_as_initiator() {
+            &mut self.cipherstates.0
+        } else {
+            &mut self.cipherstates.1
+        };
+        let len = cipher.encrypt_ad_in_place(&[], message, payload_len)?;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Like [`read_message`](Self::read_message), but the payload is decrypted in place
+    /// at the start of `message`.
+    ///
+    /// Returns the length of the payload in `message`. A failed read leaves the session as it
+    /// was, as with `read_message()`, but the contents of `message` are unspecified.
+    ///
+    /// # Errors
+    /// Will result in `Error::Input` if the message is longer than the max message length
+    /// (65535 bytes, or the one set with `Builder::max_message_len()`).
+    ///
+    /// Will result in `Error::Decrypt` if the contents couldn't be decrypted and/or the
+    /// authentication tag didn't verify.
+    ///
+    /// Will result in `StateProblem::Exhausted` if the max nonce overflows.
+    ///
+    /// Will result in `StateProblem::Closed` if the message was written with
+    /// [`write_close`](Self::write_close), and for every read after that.
+    pub fn read_message_in_place(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
+        } else if message.len() == TAGLEN {
+            // Could be a close message, which has to be tried against the untouched tag.
+            let mut tag = [0u8; TAGLEN];
+            tag.copy_from_slice(message);
+            return self.read_message(&tag, &mut []);
+        }
+        self.check_read()?;
+
+        let cipher = if self.receives_as_initiator() {
+            &mut self.cipherstates.0
+        } else {
+            &mut self.cipherstates.1
+        };
+        let len = cipher.decrypt_ad_in_place(&[], message)?;
+        self.finish_read(message.len());
+        Ok(len)
+    }
+
+    /// Close the session in the sending direction by writing a final message to `message`,
+    /// which the peer's `read_message()` reports as `StateProblem::Closed`. This lets the peer
+    /// tell an orderly shutdown from a truncated stream.
+    ///
+    /// The close message is an encrypted empty payload with associated data that regular
+    /// messages don't have, so it takes a nonce like any other message and can't be forged.
+    /// Every write after it fails with `StateProblem::Closed`; reading is unaffected.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `message` is shorter than the tag length.
+    pub fn write_close(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        self.check_write()?;
+        let cipher = if self.sends_as_initiator() {
+            &mut self.cipherstates.0
+        } else {
+            &mut self.cipherstates.1
+        };
+        let len = cipher.encrypt_with_ad(CLOSE_AD, &[], message)?;
+        self.send_closed = true;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Check whether a tag-only `message` that failed to decrypt as a regular message is a
+    /// close message.
+    fn read_close(&mut self, message: &[u8]) -> Result<usize, Error> {
+        let cipher = if self.receives_as_initiator() {
+            &mut self.cipherstates.0
+        } else {
+            &mut self.cipherstates.1
+        };
+        cipher.decrypt_with_ad(CLOSE_AD, message, &mut [])?;
+        self.recv_closed = true;
+        self.finish_read(message.len());
+        Err(StateProblem::Closed.into())
+    }
+
+    /// Like [`write_message`](Self::write_message), but the payload is sent with its length
+    /// and padded up to a multiple of `block` bytes, to hide its exact size. A `block` larger
+    /// than any payload pads every message to the same size. Read it with
+    /// [`read_message_padded`](Self::read_message_padded).
+    ///
+    /// The padded payload is capped at the max message length, so payloads near that limit
+    /// are padded less.
+    ///
+    /// Returns the number of bytes written to `message`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `Error::Input` if `block` is zero, or if `message` can't hold the padded
+    /// payload or the payload and its length don't fit in the max message length.
+    pub fn write_message_padded(
+        &mut self,
+        payload: &[u8],
+        block: usize,
+        message: &mut [u8],
+    ) -> Result<usize, Error> {
+        let padded = crate::utils::pad(payload, block, self.max_message_len - TAGLEN, message)?;
+        self.write_message_in_place(message, padded)
+    }
+
+    /// Like [`read_message`](Self::read_message), but for messages written with
+    /// [`write_message_padded`](Self::write_message_padded): the length and padding are
+    /// stripped before the payload is returned.
+    ///
+    /// `payload` must be able to hold the padded payload, i.e. `message.len() - TAGLEN` bytes.
+    ///
+    /// Returns the number of bytes of payload at the start of `payload`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in the errors of [`read_message`](Self::read_message), and in
+    /// `Error::Input` if the decrypted payload isn't correctly framed. Such a message did
+    /// authenticate, so unlike a failed decryption it still uses up its nonce.
+    pub fn read_message_padded(
+        &mut self,
+        message: &[u8],
+        payload: &mut [u8],
+    ) -> Result<usize, Error> {
+        let len = self.read_message(message, payload)?;
+        crate::utils::unpad(&mut payload[..len])
     }
 
     /// Generate a new key for the egress symmetric cipher according to Section 4.2
     /// of the Noise Specification. Synchronizing timing of rekey between initiator and
     /// responder is the responsibility of the application, as described in Section 11.3
     /// of the Noise Specification.
+    ///
+    /// This also restarts the outgoing count of an automatic [`RekeyPolicy`].
     pub fn rekey_outgoing(&mut self) {
-        if self.initiator {
+        self.sent = 0;
+        self.stats.rekeys += 1;
+        self.observer.rekey();
+        if self.sends_as_initiator() {
             self.cipherstates.rekey_initiator();
         } else {
             self.cipherstates.rekey_responder();
@@ -105,11 +563,16 @@ impl TransportState {
     /// of the Noise Specification. Synchronizing timing of rekey between initiator and
     /// responder is the responsibility of the application, as described in Section 11.3
     /// of the Noise Specification.
+    ///
+    /// This also restarts the incoming count of an automatic [`RekeyPolicy`].
     pub fn rekey_incoming(&mut self) {
-        if self.initiator {
-            self.cipherstates.rekey_responder();
-        } else {
+        self.received = 0;
+        self.stats.rekeys += 1;
+        self.observer.rekey();
+        if self.receives_as_initiator() {
             self.cipherstates.rekey_initiator();
+        } else {
+            self.cipherstates.rekey_responder();
         }
     }
 
@@ -129,20 +592,24 @@ impl TransportState {
 
     /// Set a new key for the initiator-egress symmetric cipher.
     pub fn rekey_initiator_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        self.stats.rekeys += 1;
+        self.observer.rekey();
         self.cipherstates.rekey_initiator_manually(key);
     }
 
     /// Set a new key for the responder-egress symmetric cipher.
     pub fn rekey_responder_manually(&mut self, key: &[u8; CIPHERKEYLEN]) {
+        self.stats.rekeys += 1;
+        self.observer.rekey();
         self.cipherstates.rekey_responder_manually(key);
     }
 
     /// Set the forthcoming *inbound* nonce value. Useful for using noise on lossy transports.
     pub fn set_receiving_nonce(&mut self, nonce: u64) {
-        if self.initiator {
-            self.cipherstates.1.set_nonce(nonce);
-        } else {
+        if self.receives_as_initiator() {
             self.cipherstates.0.set_nonce(nonce);
+        } else {
+            self.cipherstates.1.set_nonce(nonce);
         }
     }
 
@@ -153,10 +620,10 @@ impl TransportState {
     /// Will result in `Error::State` if not in transport mode.
     #[must_use]
     pub fn receiving_nonce(&self) -> u64 {
-        if self.initiator {
-            self.cipherstates.1.nonce()
-        } else {
+        if self.receives_as_initiator() {
             self.cipherstates.0.nonce()
+        } else {
+            self.cipherstates.1.nonce()
         }
     }
 
@@ -167,18 +634,354 @@ impl TransportState {
     /// Will result in `Error::State` if not in transport mode.
     #[must_use]
     pub fn sending_nonce(&self) -> u64 {
-        if self.initiator {
+        if self.sends_as_initiator() {
             self.cipherstates.0.nonce()
         } else {
             self.cipherstates.1.nonce()
         }
     }
 
+    /// Get the traffic counters of this session, e.g. to decide when to re-handshake.
+    #[must_use]
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+
+    /// Get the time since the handshake finished, i.e. since this transport state was created.
+    #[cfg(all(feature = "std", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        self.established.elapsed()
+    }
+
     /// Check if this session was started with the "initiator" role.
     #[must_use]
     pub fn is_initiator(&self) -> bool {
         self.initiator
     }
+
+    /// Check if this session is in half-duplex mode, see
+    /// `HandshakeState::into_half_duplex_transport_mode()`.
+    #[must_use]
+    pub fn is_half_duplex(&self) -> bool {
+        self.half_duplex.is_some()
+    }
+
+    /// Switch this session over to the keys of `handshake`, a finished re-handshake that was
+    /// run inside it and built with [`Builder::bind_to_session()`](crate::Builder::bind_to_session).
+    /// Half-duplex mode carries over.
+    ///
+    /// Messages written before the switch must be read before it, so the peers have to agree
+    /// on where it happens, e.g. by switching right after the last handshake message.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::NotBoundToSession` if `handshake` wasn't bound to this
+    /// session, or in the errors of `HandshakeState::into_transport_mode()` (or
+    /// `into_half_duplex_transport_mode()`). Either way this session is left unchanged.
+    pub fn complete_rehandshake(&mut self, handshake: HandshakeState) -> Result<(), Error> {
+        let bound = handshake
+            .bound_hash
+            .as_ref()
+            .is_some_and(|hash| hash.as_slice().ct_eq(self.get_handshake_hash()).into());
+        if !bound {
+            return Err(StateProblem::NotBoundToSession.into());
+        }
+        *self = if self.half_duplex.is_some() {
+            Self::new_half_duplex(handshake)?
+        } else {
+            Self::new(handshake)?
+        };
+        Ok(())
+    }
+
+    /// Split the session into a [`SendHalf`] and a [`RecvHalf`] that each own the cipher of
+    /// their direction, so that reading and writing can be done from different tasks or
+    /// threads without a lock around the whole session.
+    ///
+    /// Each half keeps the rekey policy, max message length and close state of its direction.
+    /// Its [`stats()`](SendHalf::stats) carry on from the ones of this session, with only the
+    /// counters of its own direction updated from then on. The remote static key, handshake
+    /// hash and exporter don't carry over, so get what's needed from them first.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `StateProblem::HalfDuplex` in half-duplex mode, where both directions
+    /// share one cipher. The session is handed back with the error, unchanged, so check
+    /// [`is_half_duplex()`](Self::is_half_duplex) first to avoid the round trip.
+    #[allow(clippy::result_large_err)]
+    pub fn split(self) -> Result<(SendHalf, RecvHalf), (Error, TransportState)> {
+        if self.half_duplex.is_some() {
+            return Err((StateProblem::HalfDuplex.into(), self));
+        }
+        let TransportState {
+            cipherstates: CipherStates(initiator_cipher, responder_cipher),
+            pattern,
+            initiator,
+            rekey_policy,
+            max_message_len,
+            sent,
+            received,
+            send_closed,
+            recv_closed,
+            stats,
+            observer,
+            ..
+        } = self;
+        let (send_cipher, recv_cipher) = if initiator {
+            (initiator_cipher, responder_cipher)
+        } else {
+            (responder_cipher, initiator_cipher)
+        };
+
+        let send = SendHalf {
+            cipher: send_cipher,
+            one_way: !initiator && pattern.is_oneway(),
+            rekey_policy,
+            max_message_len,
+            sent,
+            closed: send_closed,
+            stats,
+            observer: observer.clone(),
+        };
+        let recv = RecvHalf {
+            cipher: recv_cipher,
+            one_way: initiator && pattern.is_oneway(),
+            rekey_policy,
+            max_message_len,
+            received,
+            closed: recv_closed,
+            stats,
+            observer,
+        };
+        Ok((send, recv))
+    }
+}
+
+/// The sending half of a [`TransportState`], see [`TransportState::split()`].
+pub struct SendHalf {
+    cipher:          CipherState,
+    /// Whether this is the responder of a one-way pattern, which can't send.
+    one_way:         bool,
+    rekey_policy:    Option<RekeyPolicy>,
+    max_message_len: usize,
+    sent:            u64,
+    closed:          bool,
+    stats:           TransportStats,
+    observer:        Observer,
+}
+
+impl SendHalf {
+    fn check_write(&self) -> Result<(), Error> {
+        if self.one_way {
+            Err(StateProblem::OneWay.into())
+        } else if self.closed {
+            Err(StateProblem::Closed.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn finish_write(&mut self, len: usize) {
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += len as u64;
+        self.observer.message_written(len);
+        if let Some(policy) = self.rekey_policy {
+            if policy.advance(&mut self.sent, len) {
+                self.rekey();
+            }
+        }
+    }
+
+    /// The number of bytes every transport message adds on top of its payload, i.e. the size
+    /// of the authentication tag.
+    #[must_use]
+    pub const fn message_overhead(&self) -> usize {
+        TAGLEN
+    }
+
+    /// Like [`TransportState::write_message()`].
+    ///
+    /// # Errors
+    ///
+    /// As [`TransportState::write_message()`].
+    pub fn write_message(&mut self, payload: &[u8], message: &mut [u8]) -> Result<usize, Error> {
+        self.check_write()?;
+        if payload.len() + TAGLEN > self.max_message_len || payload.len() + TAGLEN > message.len() {
+            return Err(Error::Input);
+        }
+        let len = self.cipher.encrypt(payload, message)?;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Like [`TransportState::write_message_in_place()`].
+    ///
+    /// # Errors
+    ///
+    /// As [`TransportState::write_message_in_place()`].
+    pub fn write_message_in_place(
+        &mut self,
+        message: &mut [u8],
+        payload_len: usize,
+    ) -> Result<usize, Error> {
+        self.check_write()?;
+        if payload_len + TAGLEN > self.max_message_len || payload_len + TAGLEN > message.len() {
+            return Err(Error::Input);
+        }
+        let len = self.cipher.encrypt_ad_in_place(&[], message, payload_len)?;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Like [`TransportState::write_close()`].
+    ///
+    /// # Errors
+    ///
+    /// As [`TransportState::write_close()`].
+    pub fn write_close(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        self.check_write()?;
+        let len = self.cipher.encrypt_with_ad(CLOSE_AD, &[], message)?;
+        self.closed = true;
+        self.finish_write(len);
+        Ok(len)
+    }
+
+    /// Like [`TransportState::rekey_outgoing()`].
+    pub fn rekey(&mut self) {
+        self.sent = 0;
+        self.stats.rekeys += 1;
+        self.observer.rekey();
+        self.cipher.rekey();
+    }
+
+    /// Get the forthcoming outbound nonce value.
+    #[must_use]
+    pub fn sending_nonce(&self) -> u64 {
+        self.cipher.nonce()
+    }
+
+    /// Get the traffic counters of this half, see [`TransportState::split()`].
+    #[must_use]
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+impl fmt::Debug for SendHalf {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("SendHalf").finish()
+    }
+}
+
+/// The receiving half of a [`TransportState`], see [`TransportState::split()`].
+pub struct RecvHalf {
+    cipher:          CipherState,
+    /// Whether this is the initiator of a one-way pattern, which can't receive.
+    one_way:         bool,
+    rekey_policy:    Option<RekeyPolicy>,
+    max_message_len: usize,
+    received:        u64,
+    closed:          bool,
+    stats:           TransportStats,
+    observer:        Observer,
+}
+
+impl RecvHalf {
+    fn check_read(&self) -> Result<(), Error> {
+        if self.one_way {
+            Err(StateProblem::OneWay.into())
+        } else if self.closed {
+            Err(StateProblem::Closed.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn finish_read(&mut self, len: usize) {
+        self.stats.messages_received += 1;
+        self.stats.bytes_received += len as u64;
+        self.observer.message_read(len);
+        if let Some(policy) = self.rekey_policy {
+            if policy.advance(&mut self.received, len) {
+                self.rekey();
+            }
+        }
+    }
+
+    /// Like [`TransportState::read_message()`].
+    ///
+    /// # Errors
+    ///
+    /// As [`TransportState::read_message()`].
+    pub fn read_message(&mut self, message: &[u8], payload: &mut [u8]) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
+        }
+        self.check_read()?;
+        let len = match self.cipher.decrypt(message, payload) {
+            Err(Error::Decrypt { token: None }) if message.len() == TAGLEN => {
+                self.cipher.decrypt_with_ad(CLOSE_AD, message, &mut [])?;
+                self.closed = true;
+                self.finish_read(message.len());
+                return Err(StateProblem::Closed.into());
+            },
+            res => res?,
+        };
+        self.finish_read(message.len());
+        Ok(len)
+    }
+
+    /// Like [`TransportState::read_message_in_place()`].
+    ///
+    /// # Errors
+    ///
+    /// As [`TransportState::read_message_in_place()`].
+    pub fn read_message_in_place(&mut self, message: &mut [u8]) -> Result<usize, Error> {
+        if message.len() > self.max_message_len {
+            return Err(Error::Input);
+        } else if message.len() == TAGLEN {
+            // Could be a close message, which has to be tried against the untouched tag.
+            let mut tag = [0u8; TAGLEN];
+            tag.copy_from_slice(message);
+            return self.read_message(&tag, &mut []);
+        }
+        self.check_read()?;
+        let len = self.cipher.decrypt_ad_in_place(&[], message)?;
+        self.finish_read(message.len());
+        Ok(len)
+    }
+
+    /// Like [`TransportState::rekey_incoming()`].
+    pub fn rekey(&mut self) {
+        self.received = 0;
+        self.stats.rekeys += 1;
+        self.observer.rekey();
+        self.cipher.rekey();
+    }
+
+    /// Set the forthcoming inbound nonce value. Useful for using noise on lossy transports.
+    pub fn set_receiving_nonce(&mut self, nonce: u64) {
+        self.cipher.set_nonce(nonce);
+    }
+
+    /// Get the forthcoming inbound nonce value.
+    #[must_use]
+    pub fn receiving_nonce(&self) -> u64 {
+        self.cipher.nonce()
+    }
+
+    /// Get the traffic counters of this half, see [`TransportState::split()`].
+    #[must_use]
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+impl fmt::Debug for RecvHalf {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RecvHalf").finish()
+    }
 }
 
 impl fmt::Debug for TransportState {
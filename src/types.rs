@@ -1,14 +1,21 @@
 //! The traits for cryptographic implementations that can be used by Noise.
 
 use crate::{
-    constants::{CIPHERKEYLEN, MAXBLOCKLEN, MAXHASHLEN, TAGLEN},
+    constants::{CIPHERKEYLEN, MAXBLOCKLEN, MAXHASHLEN, MAXMSGLEN, TAGLEN},
     Error,
 };
 use rand_core::{CryptoRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
 
 /// CSPRNG operations
+///
+/// Implemented for every `rand_core` RNG that is `CryptoRng + Send + Sync`, so any such RNG
+/// can be boxed and returned from `CryptoResolver::resolve_rng()` or passed to
+/// `Builder::rng()` as it is.
 pub trait Random: CryptoRng + RngCore + Send + Sync {}
 
+impl<T: CryptoRng + RngCore + Send + Sync> Random for T {}
+
 /// Diffie-Hellman operations
 pub trait Dh: Send + Sync {
     /// The string that the Noise spec defines for the primitive
@@ -34,8 +41,14 @@ pub trait Dh: Send + Sync {
 
     /// Calculate a Diffie-Hellman exchange.
     ///
+    /// This may be done elsewhere, e.g. by a hardware device or a remote key service holding
+    /// the private key (see `Builder::local_static_dh()`). The handshake passes an error on
+    /// from the `write_message()` or `read_message()` call that needed the DH and stays at the
+    /// same message, so a transient failure can be retried by making the same call again.
+    ///
     /// # Errors
-    /// Returns `Error::Dh` in the event that the Diffie-Hellman failed.
+    /// Returns `Error::Dh { token: None }` in the event that the Diffie-Hellman failed. The
+    /// handshake fills in the token the DH was for.
     fn dh(&self, pubkey: &[u8], out: &mut [u8]) -> Result<(), Error>;
 }
 
@@ -53,7 +66,7 @@ pub trait Cipher: Send + Sync {
     /// Decrypt (with associated data) a given ciphertext.
     ///
     /// # Errors
-    /// Returns `Error::Decrypt` in the event that the decryption failed.
+    /// Returns `Error::Decrypt { token: None }` in the event that the decryption failed.
     fn decrypt(
         &self,
         nonce: u64,
@@ -62,15 +75,58 @@ pub trait Cipher: Send + Sync {
         out: &mut [u8],
     ) -> Result<usize, Error>;
 
+    /// Encrypt (with associated data) the plaintext in `in_out[..plaintext_len]`, writing the
+    /// ciphertext and tag back into `in_out`.
+    ///
+    /// The default implementation copies the plaintext to a stack buffer and calls
+    /// [`encrypt`](Cipher::encrypt), so `plaintext_len` must not exceed `MAXMSGLEN`;
+    /// backends that can work in place should override it.
+    fn encrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        let mut plaintext = [0u8; MAXMSGLEN];
+        copy_slices!(in_out[..plaintext_len], plaintext);
+        let len = self.encrypt(nonce, authtext, &plaintext[..plaintext_len], in_out);
+        plaintext[..plaintext_len].zeroize();
+        len
+    }
+
+    /// Decrypt (with associated data) the ciphertext in `in_out`, writing the plaintext to
+    /// the start of `in_out`.
+    ///
+    /// The default implementation copies the ciphertext to a stack buffer and calls
+    /// [`decrypt`](Cipher::decrypt); backends that can work in place should override it.
+    ///
+    /// # Errors
+    /// Returns `Error::Decrypt` in the event that the decryption failed, or if `in_out` is
+    /// shorter than `TAGLEN` or longer than `MAXMSGLEN`.
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        in_out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if in_out.len() < TAGLEN || in_out.len() > MAXMSGLEN {
+            return Err(Error::Decrypt { token: None });
+        }
+        let mut ciphertext = [0u8; MAXMSGLEN];
+        copy_slices!(in_out, ciphertext);
+        self.decrypt(nonce, authtext, &ciphertext[..in_out.len()], in_out)
+    }
+
     /// Rekey according to Section 4.2 of the Noise Specification, with a default
     /// implementation guaranteed to be secure for all ciphers.
     fn rekey(&mut self) {
-        let mut ciphertext = [0; CIPHERKEYLEN + TAGLEN];
-        let ciphertext_len = self.encrypt(u64::MAX, &[], &[0; CIPHERKEYLEN], &mut ciphertext);
+        let mut ciphertext = Zeroizing::new([0; CIPHERKEYLEN + TAGLEN]);
+        let ciphertext_len = self.encrypt(u64::MAX, &[], &[0; CIPHERKEYLEN], &mut *ciphertext);
         assert_eq!(ciphertext_len, ciphertext.len());
 
         // TODO(mcginty): use `split_array_ref` once stable to avoid memory inefficiency
-        let mut key = [0u8; CIPHERKEYLEN];
+        let mut key = Zeroizing::new([0u8; CIPHERKEYLEN]);
         key.copy_from_slice(&ciphertext[..CIPHERKEYLEN]);
 
         self.set(&key);
@@ -104,8 +160,8 @@ pub trait Hash: Send + Sync {
         assert!(key.len() <= self.block_len());
         let block_len = self.block_len();
         let hash_len = self.hash_len();
-        let mut ipad = [0x36u8; MAXBLOCKLEN];
-        let mut opad = [0x5cu8; MAXBLOCKLEN];
+        let mut ipad = Zeroizing::new([0x36u8; MAXBLOCKLEN]);
+        let mut opad = Zeroizing::new([0x5cu8; MAXBLOCKLEN]);
         for count in 0..key.len() {
             ipad[count] ^= key[count];
             opad[count] ^= key[count];
@@ -113,8 +169,8 @@ pub trait Hash: Send + Sync {
         self.reset();
         self.input(&ipad[..block_len]);
         self.input(data);
-        let mut inner_output = [0u8; MAXHASHLEN];
-        self.result(&mut inner_output);
+        let mut inner_output = Zeroizing::new([0u8; MAXHASHLEN]);
+        self.result(&mut *inner_output);
         self.reset();
         self.input(&opad[..block_len]);
         self.input(&inner_output[..hash_len]);
@@ -134,25 +190,25 @@ pub trait Hash: Send + Sync {
         out3: &mut [u8],
     ) {
         let hash_len = self.hash_len();
-        let mut temp_key = [0u8; MAXHASHLEN];
-        self.hmac(chaining_key, input_key_material, &mut temp_key);
-        self.hmac(&temp_key, &[1u8], out1);
+        let mut temp_key = Zeroizing::new([0u8; MAXHASHLEN]);
+        self.hmac(chaining_key, input_key_material, &mut *temp_key);
+        self.hmac(&temp_key[..], &[1u8], out1);
         if outputs == 1 {
             return;
         }
 
-        let mut in2 = [0u8; MAXHASHLEN + 1];
+        let mut in2 = Zeroizing::new([0u8; MAXHASHLEN + 1]);
         copy_slices!(out1[0..hash_len], &mut in2);
         in2[hash_len] = 2;
-        self.hmac(&temp_key, &in2[..=hash_len], out2);
+        self.hmac(&temp_key[..], &in2[..=hash_len], out2);
         if outputs == 2 {
             return;
         }
 
-        let mut in3 = [0u8; MAXHASHLEN + 1];
+        let mut in3 = Zeroizing::new([0u8; MAXHASHLEN + 1]);
         copy_slices!(out2[0..hash_len], &mut in3);
         in3[hash_len] = 3;
-        self.hmac(&temp_key, &in3[..=hash_len], out3);
+        self.hmac(&temp_key[..], &in3[..=hash_len], out3);
     }
 }
 
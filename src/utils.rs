@@ -1,8 +1,13 @@
-use std::ops::{Deref, DerefMut};
+use crate::error::Error;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+use subtle::{Choice, ConstantTimeEq};
 
 /// Toggle is similar to Option, except that even in the Off/"None" case, there is still
 /// an owned allocated inner object. This is useful for holding onto pre-allocated objects
 /// that can be toggled as enabled.
+#[derive(Clone)]
 pub struct Toggle<T> {
     inner: T,
     on:    bool,
@@ -47,3 +52,61 @@ impl<T> DerefMut for Toggle<T> {
         &mut self.inner
     }
 }
+
+#[cfg(feature = "std")]
+/// Copy the fragments of a vectored payload to the start of `out`, returning their total length.
+pub(crate) fn gather(payload: &[IoSlice<'_>], out: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+    for fragment in payload {
+        let end = len + fragment.len();
+        if end > out.len() {
+            return Err(Error::Input);
+        }
+        out[len..end].copy_from_slice(fragment);
+        len = end;
+    }
+    Ok(len)
+}
+
+/// Whether `psk` is a single repeated byte (e.g. all zeros), which can't be a uniformly random
+/// key. Runs in constant time, so it doesn't leak how much of the PSK repeats.
+pub(crate) fn is_weak_psk(psk: &[u8]) -> bool {
+    psk.iter().fold(Choice::from(1), |acc, b| acc & b.ct_eq(&psk[0])).into()
+}
+
+/// Write `payload` to the start of `out` behind a 2-byte big-endian length, and zero-pad it to
+/// a multiple of `block` bytes (but no more than `max_len`), returning the padded length.
+pub(crate) fn pad(
+    payload: &[u8],
+    block: usize,
+    max_len: usize,
+    out: &mut [u8],
+) -> Result<usize, Error> {
+    let len = u16::try_from(payload.len()).map_err(|_| Error::Input)?;
+    let unpadded = payload.len() + 2;
+    if block == 0 || unpadded > max_len {
+        return Err(Error::Input);
+    }
+    let padded = unpadded.div_ceil(block).saturating_mul(block).min(max_len);
+    if padded > out.len() {
+        return Err(Error::Input);
+    }
+    out[..2].copy_from_slice(&len.to_be_bytes());
+    out[2..unpadded].copy_from_slice(payload);
+    out[unpadded..padded].fill(0);
+    Ok(padded)
+}
+
+/// Strip the length and padding added by [`pad`] from `plaintext`, moving the payload to its
+/// start and returning the payload length.
+pub(crate) fn unpad(plaintext: &mut [u8]) -> Result<usize, Error> {
+    if plaintext.len() < 2 {
+        return Err(Error::Input);
+    }
+    let len = usize::from(u16::from_be_bytes([plaintext[0], plaintext[1]]));
+    if len + 2 > plaintext.len() {
+        return Err(Error::Input);
+    }
+    plaintext.copy_within(2..len + 2, 0);
+    Ok(len)
+}
@@ -0,0 +1,642 @@
+//! A runner for the JSON test vector format shared by
+//! [cacophony](https://github.com/haskell-cryptography/cacophony) and
+//! [noise-c](https://github.com/rweather/noise-c).
+//!
+//! Each vector is replayed by building an initiator and a responder from the vector's keys,
+//! exchanging every message and checking the ciphertexts, payloads and (when present) the
+//! handshake hash. Passing a custom resolver to [`Vector::run_with_resolver`] makes this
+//! usable for checking third-party crypto backends as well.
+//!
+//! Fallback vectors (Noise Pipes) are replayed by having the responder reject the first
+//! message and then moving both sides over with [`HandshakeState::into_fallback`](crate::HandshakeState::into_fallback).
+//!
+//! [`Vector::record`] goes the other way: it runs the keys, prologues, PSKs and payloads of a
+//! vector and fills in the messages, for generating vectors for other implementations. The
+//! resulting [`Transcript`] also holds the symmetric state after every operation of the
+//! handshake and the final cipher keys, to find the first step where two implementations
+//! disagree.
+//!
+//! Hybrid forward secrecy handshakes (`hfs` protocol names with a KEM, e.g.
+//! `Noise_XXhfs_25519+Kyber1024_ChaChaPoly_BLAKE2s`) are driven through their KEM tokens, but
+//! since KEM encapsulation is randomized only the payloads are checked for them. Vectors using
+//! the older DH-based `hybrid` field aren't supported.
+//!
+//! ```rust,ignore
+//! let vectors = snow::vectors::Vectors::from_json(&std::fs::read_to_string("cacophony.txt")?)?;
+//! for vector in &vectors.vectors {
+//!     vector.run_with_resolver(|| Box::new(MyResolver))?;
+//! }
+//! ```
+
+use crate::{
+    constants::{CIPHERKEYLEN, MAXMSGLEN, PSKLEN},
+    error::Error,
+    handshakestate::HandshakeState,
+    params::{HandshakeModifier, NoiseParams},
+    resolvers::BoxedCryptoResolver,
+    Builder,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// A file of test vectors, as found in e.g. `cacophony.txt`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Vectors {
+    /// The vectors contained in the file.
+    pub vectors: Vec<Vector>,
+}
+
+impl Vectors {
+    /// Parse a test vector file.
+    ///
+    /// # Errors
+    ///
+    /// Will result in an error if `json` isn't a well-formed test vector file.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Write the vectors out in the same format [`Vectors::from_json`] reads.
+    ///
+    /// # Errors
+    ///
+    /// Will result in an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// A single message of a test vector.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VectorMessage {
+    /// The plaintext payload.
+    #[serde(with = "hex_bytes")]
+    pub payload:    Vec<u8>,
+    /// The expected message on the wire.
+    #[serde(with = "hex_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// A single test vector.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Vector {
+    /// An optional human readable name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name:               Option<String>,
+    /// The full Noise protocol name, e.g. `Noise_XX_25519_ChaChaPoly_BLAKE2s`.
+    pub protocol_name:      String,
+    /// The DH-based hybrid forward secrecy algorithm, if any. Not supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hybrid:             Option<String>,
+    /// Whether the handshake is expected to fail.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fail:               bool,
+    /// Whether the responder is expected to reject the first message and switch both sides
+    /// over to `fallback_pattern`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fallback:           bool,
+    /// The handshake pattern to fall back to, e.g. `XXfallback`, or a full protocol name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_pattern:   Option<String>,
+    /// The initiator's prologue.
+    #[serde(with = "hex_bytes")]
+    pub init_prologue:      Vec<u8>,
+    /// The initiator's PSKs, in the order of the protocol name's `pskN` modifiers.
+    #[serde(default, with = "hex_list", skip_serializing_if = "Vec::is_empty")]
+    pub init_psks:          Vec<Vec<u8>>,
+    /// The initiator's static private key.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub init_static:        Option<Vec<u8>>,
+    /// The initiator's ephemeral private key.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub init_ephemeral:     Option<Vec<u8>>,
+    /// The responder's static public key, as known in advance by the initiator.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub init_remote_static: Option<Vec<u8>>,
+    /// The responder's prologue.
+    #[serde(with = "hex_bytes")]
+    pub resp_prologue:      Vec<u8>,
+    /// The responder's PSKs, in the order of the protocol name's `pskN` modifiers.
+    #[serde(default, with = "hex_list", skip_serializing_if = "Vec::is_empty")]
+    pub resp_psks:          Vec<Vec<u8>>,
+    /// The responder's static private key.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub resp_static:        Option<Vec<u8>>,
+    /// The responder's ephemeral private key.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub resp_ephemeral:     Option<Vec<u8>>,
+    /// The initiator's static public key, as known in advance by the responder.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub resp_remote_static: Option<Vec<u8>>,
+    /// The expected handshake hash, if given.
+    #[serde(default, with = "hex_opt", skip_serializing_if = "Option::is_none")]
+    pub handshake_hash:     Option<Vec<u8>>,
+    /// The messages, starting with the initiator's first handshake message.
+    pub messages:           Vec<VectorMessage>,
+}
+
+/// An operation of the symmetric state during a handshake, see
+/// <https://noiseprotocol.org/noise.html#the-symmetricstate-object>.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SymmetricOperation {
+    /// `InitializeSymmetric()` with the protocol name.
+    Initialize,
+    /// `MixHash()`, including the ones made by `EncryptAndHash()`, `DecryptAndHash()` and
+    /// `MixKeyAndHash()`.
+    MixHash,
+    /// `MixKey()`.
+    MixKey,
+    /// `MixKeyAndHash()`, after its own `MixHash()`.
+    MixKeyAndHash,
+}
+
+/// The symmetric state right after an operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SymmetricStep {
+    /// The operation.
+    pub operation: SymmetricOperation,
+    /// The handshake hash `h` after the operation.
+    #[serde(with = "hex_secret")]
+    pub h:         Zeroizing<Vec<u8>>,
+    /// The chaining key `ck` after the operation.
+    #[serde(with = "hex_secret")]
+    pub ck:        Zeroizing<Vec<u8>>,
+}
+
+/// What a `SymmetricState` has done so far, kept only for the states built by
+/// [`Vector::record`].
+#[derive(Default)]
+pub(crate) struct SymmetricTrace {
+    pub(crate) steps: Vec<SymmetricStep>,
+    /// The cipher keys of the initiator and the responder, once split.
+    pub(crate) split: Option<Zeroizing<[[u8; CIPHERKEYLEN]; 2]>>,
+}
+
+/// A recorded run of a vector, see [`Vector::record`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Transcript {
+    /// The vector, with the messages, and the handshake hash unless encapsulation is
+    /// randomized, filled in from the run.
+    pub vector:             Vector,
+    /// The initiator's symmetric state after each operation of its handshake. The
+    /// responder's goes through the same steps.
+    pub steps:              Vec<SymmetricStep>,
+    /// The key of the initiator's sending cipher after the handshake.
+    #[serde(with = "hex_secret")]
+    pub initiator_send_key: Zeroizing<Vec<u8>>,
+    /// The key of the responder's sending cipher after the handshake.
+    #[serde(with = "hex_secret")]
+    pub responder_send_key: Zeroizing<Vec<u8>>,
+}
+
+impl Transcript {
+    /// Write the transcript out as JSON, with the vector in the format
+    /// [`Vectors::from_json`] reads.
+    ///
+    /// # Errors
+    ///
+    /// Will result in an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// The reason a [`Vector`] didn't pass.
+#[derive(Debug)]
+pub enum VectorError {
+    /// The vector uses a feature (DH-based hybrid forward secrecy, or a fallback without a
+    /// `fallback_pattern`) that the runner doesn't support.
+    Unsupported,
+    /// The vector's keys or protocol name were rejected while building the sessions.
+    Setup(Error),
+    /// Writing or reading message `index` failed.
+    Message {
+        /// The index of the failing message.
+        index: usize,
+        /// The underlying error.
+        error: Error,
+    },
+    /// Message `index` didn't match the vector's ciphertext or payload.
+    Mismatch {
+        /// The index of the mismatching message.
+        index: usize,
+    },
+    /// The responder accepted the first message of a fallback vector.
+    NoFallback,
+    /// The vector ran out of messages before the handshake finished.
+    Truncated,
+    /// The handshake hash differed from the vector's.
+    HandshakeHash,
+    /// The vector was expected to fail, but passed.
+    UnexpectedSuccess,
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::Unsupported => write!(f, "unsupported vector"),
+            VectorError::Setup(error) => write!(f, "setup failed: {error}"),
+            VectorError::Message { index, error } => write!(f, "message {index} failed: {error}"),
+            VectorError::Mismatch { index } => write!(f, "message {index} mismatched"),
+            VectorError::NoFallback => write!(f, "responder accepted the fallback trigger"),
+            VectorError::Truncated => write!(f, "vector ended before the handshake finished"),
+            VectorError::HandshakeHash => write!(f, "handshake hash mismatched"),
+            VectorError::UnexpectedSuccess => write!(f, "vector was expected to fail"),
+        }
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+impl Vector {
+    /// Replay the vector using the default resolver.
+    ///
+    /// # Errors
+    ///
+    /// Will result in a [`VectorError`] describing the first point where the vector and
+    /// the library disagree.
+    #[cfg(feature = "default-resolver")]
+    pub fn run(&self) -> Result<(), VectorError> {
+        self.run_with_resolver(|| Box::new(crate::resolvers::DefaultResolver))
+    }
+
+    /// Replay the vector, building both sessions with resolvers produced by `resolver`.
+    ///
+    /// # Errors
+    ///
+    /// Will result in a [`VectorError`] describing the first point where the vector and
+    /// the library disagree.
+    pub fn run_with_resolver<F>(&self, resolver: F) -> Result<(), VectorError>
+    where
+        F: Fn() -> BoxedCryptoResolver,
+    {
+        if self.hybrid.is_some() {
+            return Err(VectorError::Unsupported);
+        }
+        match (self.fail, self.replay(&resolver)) {
+            (false, result) => result,
+            (true, Ok(())) => Err(VectorError::UnexpectedSuccess),
+            (true, Err(_)) => Ok(()),
+        }
+    }
+
+    fn replay(&self, resolver: &dyn Fn() -> BoxedCryptoResolver) -> Result<(), VectorError> {
+        let params: NoiseParams = self.protocol_name.parse().map_err(VectorError::Setup)?;
+        let (mut init, mut resp) = self.sessions(&params, resolver, false)?;
+
+        let mut checker = Checker::new(&params);
+        let mut messages = self.messages.iter().enumerate();
+        if self.fallback {
+            let fallback = self.fallback_params(&params)?;
+            let (index, message) = messages.next().ok_or(VectorError::Truncated)?;
+            let len = init
+                .write_message(&message.payload, &mut checker.message_buf)
+                .map_err(|error| VectorError::Message { index, error })?;
+            if checker.message_buf[..len] != message.ciphertext[..] {
+                return Err(VectorError::Mismatch { index });
+            }
+            if resp.read_message(&checker.message_buf[..len], &mut checker.payload_buf).is_ok() {
+                return Err(VectorError::NoFallback);
+            }
+            init = init
+                .into_fallback(fallback.clone(), &self.init_prologue)
+                .map_err(VectorError::Setup)?;
+            resp = resp.into_fallback(fallback, &self.resp_prologue).map_err(VectorError::Setup)?;
+            checker = Checker::new(&init.params);
+        }
+
+        // `init` and `resp` stay the original initiator and responder throughout, even after a
+        // fallback swaps their roles in the handshake.
+        while !init.is_handshake_finished() {
+            let (index, message) = messages.next().ok_or(VectorError::Truncated)?;
+            let (send, recv) =
+                if init.is_my_turn() { (&mut init, &mut resp) } else { (&mut resp, &mut init) };
+            checker.check(
+                index,
+                message,
+                |payload, out| send.write_message(payload, out),
+                |m, out| recv.read_message(m, out),
+            )?;
+        }
+
+        if let (Some(h), false) = (&self.handshake_hash, checker.randomized) {
+            if init.get_handshake_hash() != &h[..] || resp.get_handshake_hash() != &h[..] {
+                return Err(VectorError::HandshakeHash);
+            }
+        }
+
+        let is_oneway = init.params.handshake.pattern.is_oneway();
+        let mut init = init.into_transport_mode().map_err(VectorError::Setup)?;
+        let mut resp = resp.into_transport_mode().map_err(VectorError::Setup)?;
+        for (index, message) in messages {
+            let (send, recv) = if is_oneway || index % 2 == 0 {
+                (&mut init, &mut resp)
+            } else {
+                (&mut resp, &mut init)
+            };
+            checker.check(
+                index,
+                message,
+                |payload, out| send.write_message(payload, out),
+                |m, out| recv.read_message(m, out),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Record a run of the vector using the default resolver.
+    ///
+    /// # Errors
+    ///
+    /// As [`Vector::record_with_resolver`].
+    #[cfg(feature = "default-resolver")]
+    pub fn record(&self) -> Result<Transcript, VectorError> {
+        self.record_with_resolver(|| Box::new(crate::resolvers::DefaultResolver))
+    }
+
+    /// Run the vector's keys, prologues, PSKs and payloads through an initiator and a
+    /// responder built with resolvers produced by `resolver`, recording the messages and
+    /// the symmetric state along the way.
+    ///
+    /// The vector's own messages only give the payloads, so their ciphertexts (and the
+    /// handshake hash) may be left empty. As when replaying, handshake messages alternate
+    /// between the parties starting with the initiator, and transport messages at even
+    /// indices are the initiator's and at odd ones the responder's, unless the pattern is
+    /// one-way.
+    ///
+    /// # Errors
+    ///
+    /// Will result in `VectorError::Unsupported` for vectors that are expected to fail, fall
+    /// back or use `hybrid`, and otherwise in a [`VectorError`] describing the first step
+    /// that failed.
+    pub fn record_with_resolver<F>(&self, resolver: F) -> Result<Transcript, VectorError>
+    where
+        F: Fn() -> BoxedCryptoResolver,
+    {
+        if self.fail || self.fallback || self.hybrid.is_some() {
+            return Err(VectorError::Unsupported);
+        }
+        let params: NoiseParams = self.protocol_name.parse().map_err(VectorError::Setup)?;
+        let (mut init, mut resp) = self.sessions(&params, &resolver, true)?;
+
+        let mut checker = Checker::new(&params);
+        let mut payloads = self.messages.iter().map(|message| &message.payload[..]).enumerate();
+        let mut messages = Vec::with_capacity(self.messages.len());
+        while !init.is_handshake_finished() {
+            let (index, payload) = payloads.next().ok_or(VectorError::Truncated)?;
+            let (send, recv) =
+                if init.is_my_turn() { (&mut init, &mut resp) } else { (&mut resp, &mut init) };
+            messages.push(checker.record(
+                index,
+                payload,
+                |payload, out| send.write_message(payload, out),
+                |m, out| recv.read_message(m, out),
+            )?);
+        }
+
+        let handshake_hash = (!checker.randomized).then(|| init.get_handshake_hash().to_vec());
+        let trace = init.symmetricstate.trace.take().ok_or(VectorError::Truncated)?;
+        let steps = trace.steps;
+        let split = trace.split.ok_or(VectorError::Truncated)?;
+        let initiator_send_key = Zeroizing::new(split[0].to_vec());
+        let responder_send_key = Zeroizing::new(split[1].to_vec());
+
+        let is_oneway = init.params.handshake.pattern.is_oneway();
+        let mut init = init.into_transport_mode().map_err(VectorError::Setup)?;
+        let mut resp = resp.into_transport_mode().map_err(VectorError::Setup)?;
+        for (index, payload) in payloads {
+            let (send, recv) = if is_oneway || index % 2 == 0 {
+                (&mut init, &mut resp)
+            } else {
+                (&mut resp, &mut init)
+            };
+            messages.push(checker.record(
+                index,
+                payload,
+                |payload, out| send.write_message(payload, out),
+                |m, out| recv.read_message(m, out),
+            )?);
+        }
+
+        let vector = Vector { handshake_hash, messages, ..self.clone() };
+        Ok(Transcript { vector, steps, initiator_send_key, responder_send_key })
+    }
+
+    /// Build the initiator and the responder from the vector's keys, keeping a trace of the
+    /// initiator's symmetric state if `trace` is set.
+    fn sessions(
+        &self,
+        params: &NoiseParams,
+        resolver: &dyn Fn() -> BoxedCryptoResolver,
+        trace: bool,
+    ) -> Result<(HandshakeState, HandshakeState), VectorError> {
+        let init_psks = psks(&self.init_psks)?;
+        let resp_psks = psks(&self.resp_psks)?;
+
+        let mut init = Self::builder(
+            params,
+            resolver(),
+            &self.init_prologue,
+            &init_psks,
+            self.init_static.as_deref(),
+            self.init_ephemeral.as_deref(),
+            self.init_remote_static.as_deref(),
+        )?;
+        if trace {
+            init = init.trace_symmetric_state();
+        }
+        let init = init.build_initiator().map_err(VectorError::Setup)?;
+        let resp = Self::builder(
+            params,
+            resolver(),
+            &self.resp_prologue,
+            &resp_psks,
+            self.resp_static.as_deref(),
+            self.resp_ephemeral.as_deref(),
+            self.resp_remote_static.as_deref(),
+        )?
+        .build_responder()
+        .map_err(VectorError::Setup)?;
+        Ok((init, resp))
+    }
+
+    /// Resolve `fallback_pattern` against the original protocol name's primitives.
+    fn fallback_params(&self, params: &NoiseParams) -> Result<NoiseParams, VectorError> {
+        let pattern = self.fallback_pattern.as_deref().ok_or(VectorError::Unsupported)?;
+        let name = if pattern.starts_with("Noise_") {
+            pattern.to_owned()
+        } else {
+            let mut parts: Vec<&str> = params.name.split('_').collect();
+            parts[1] = pattern;
+            parts.join("_")
+        };
+        name.parse().map_err(VectorError::Setup)
+    }
+
+    fn builder<'a>(
+        params: &NoiseParams,
+        resolver: BoxedCryptoResolver,
+        prologue: &'a [u8],
+        psks: &'a [[u8; PSKLEN]],
+        s: Option<&'a [u8]>,
+        e: Option<&'a [u8]>,
+        rs: Option<&'a [u8]>,
+    ) -> Result<Builder<'a>, VectorError> {
+        let mut builder = Builder::with_resolver(params.clone(), resolver)
+            .prologue(prologue)
+            .map_err(VectorError::Setup)?;
+        let locations = params.handshake.modifiers.list.iter().filter_map(|m| match m {
+            HandshakeModifier::Psk(n) => Some(*n),
+            _ => None,
+        });
+        for (location, psk) in locations.zip(psks) {
+            builder = builder.psk(location, psk).map_err(VectorError::Setup)?;
+        }
+        if let Some(s) = s {
+            builder = builder.local_private_key(s).map_err(VectorError::Setup)?;
+        }
+        if let Some(e) = e {
+            builder = builder.fixed_ephemeral_key_for_testing_only(e);
+        }
+        if let Some(rs) = rs {
+            builder = builder.remote_public_key(rs).map_err(VectorError::Setup)?;
+        }
+        Ok(builder)
+    }
+}
+
+/// Buffers for replaying messages, and whether their ciphertexts can be compared at all.
+struct Checker {
+    message_buf: Vec<u8>,
+    payload_buf: Vec<u8>,
+    randomized:  bool,
+}
+
+impl Checker {
+    fn new(params: &NoiseParams) -> Self {
+        #[cfg(feature = "hfs")]
+        let randomized = params.handshake.is_hfs();
+        #[cfg(not(feature = "hfs"))]
+        let randomized = {
+            let _ = params;
+            false
+        };
+        Self { message_buf: vec![0u8; MAXMSGLEN], payload_buf: vec![0u8; MAXMSGLEN], randomized }
+    }
+
+    fn check(
+        &mut self,
+        index: usize,
+        message: &VectorMessage,
+        write: impl FnOnce(&[u8], &mut [u8]) -> Result<usize, Error>,
+        read: impl FnOnce(&[u8], &mut [u8]) -> Result<usize, Error>,
+    ) -> Result<(), VectorError> {
+        let len = write(&message.payload, &mut self.message_buf)
+            .map_err(|error| VectorError::Message { index, error })?;
+        let payload_len = read(&self.message_buf[..len], &mut self.payload_buf)
+            .map_err(|error| VectorError::Message { index, error })?;
+        if (!self.randomized && self.message_buf[..len] != message.ciphertext[..])
+            || self.payload_buf[..payload_len] != message.payload[..]
+        {
+            return Err(VectorError::Mismatch { index });
+        }
+        Ok(())
+    }
+
+    fn record(
+        &mut self,
+        index: usize,
+        payload: &[u8],
+        write: impl FnOnce(&[u8], &mut [u8]) -> Result<usize, Error>,
+        read: impl FnOnce(&[u8], &mut [u8]) -> Result<usize, Error>,
+    ) -> Result<VectorMessage, VectorError> {
+        let len = write(payload, &mut self.message_buf)
+            .map_err(|error| VectorError::Message { index, error })?;
+        let payload_len = read(&self.message_buf[..len], &mut self.payload_buf)
+            .map_err(|error| VectorError::Message { index, error })?;
+        if self.payload_buf[..payload_len] != payload[..] {
+            return Err(VectorError::Mismatch { index });
+        }
+        Ok(VectorMessage {
+            payload:    payload.to_vec(),
+            ciphertext: self.message_buf[..len].to_vec(),
+        })
+    }
+}
+
+fn psks(list: &[Vec<u8>]) -> Result<Vec<[u8; PSKLEN]>, VectorError> {
+    list.iter()
+        .map(|psk| psk[..].try_into().map_err(|_| VectorError::Setup(Error::Input)))
+        .collect()
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+mod hex_secret {
+    use serde::{Deserializer, Serializer};
+    use zeroize::Zeroizing;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        super::hex_bytes::serialize(bytes, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Zeroizing<Vec<u8>>, D::Error> {
+        super::hex_bytes::deserialize(deserializer).map(Zeroizing::new)
+    }
+}
+
+mod hex_opt {
+    use serde::{Deserializer, Serializer};
+
+    #[allow(clippy::ref_option)]
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => super::hex_bytes::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        super::hex_bytes::deserialize(deserializer).map(Some)
+    }
+}
+
+mod hex_list {
+    use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(list: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(list.len()))?;
+        for bytes in list {
+            seq.serialize_element(&hex::encode(bytes))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|s| hex::decode(s).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
@@ -0,0 +1,77 @@
+//! Constants and helpers for interoperating with [WireGuard](https://www.wireguard.com/protocol/).
+//!
+//! WireGuard's handshake is a plain `Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s` handshake with
+//! [`IDENTIFIER`] as the prologue, a [TAI64N](`tai64n`) timestamp as the first payload, an
+//! empty second payload, and an all-zero PSK when no preshared key is configured. The
+//! surrounding message fields (type, sender/receiver indices, `mac1`/`mac2`) are outside the
+//! Noise handshake and are left to the caller.
+//!
+//! # Examples
+//!
+//! ```
+//! # use snow::{wireguard, Builder};
+//! #
+//! # #[cfg(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated")))]
+//! # fn try_main() -> Result<(), snow::Error> {
+//! # let responder_public = Builder::new(wireguard::params()).generate_keypair()?.public;
+//! # let private_key = Builder::new(wireguard::params()).generate_keypair()?.private;
+//! let mut initiator = Builder::new(wireguard::params())
+//!     .prologue(wireguard::IDENTIFIER)?
+//!     .psk(2, &wireguard::NO_PSK)?
+//!     .local_private_key(&private_key)?
+//!     .remote_public_key(&responder_public)?
+//!     .build_initiator()?;
+//!
+//! let mut message = [0u8; 108];
+//! let timestamp = wireguard::tai64n(std::time::SystemTime::now());
+//! let len = initiator.write_message(&timestamp, &mut message)?;
+//! assert_eq!(len, 108);
+//! #     Ok(())
+//! # }
+//! #
+//! # #[cfg(not(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated"))))]
+//! # fn try_main() -> Result<(), ()> { Ok(()) }
+//! #
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use crate::{constants::PSKLEN, params::NoiseParams};
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The Noise protocol name WireGuard calls its "Construction".
+pub const CONSTRUCTION: &str = "Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+
+/// WireGuard's "Identifier", used as the prologue of every handshake.
+pub const IDENTIFIER: &[u8] = b"WireGuard v1 zx2c4 Jason@zx2c4.com";
+
+/// The label hashed with the responder's static public key to key `mac1`.
+pub const LABEL_MAC1: &[u8] = b"mac1----";
+
+/// The label hashed with the responder's static public key to key cookie replies.
+pub const LABEL_COOKIE: &[u8] = b"cookie--";
+
+/// The PSK WireGuard uses when the peers haven't configured a preshared key.
+pub const NO_PSK: [u8; PSKLEN] = [0u8; PSKLEN];
+
+/// The parameters of the WireGuard handshake, i.e. [`CONSTRUCTION`].
+#[must_use]
+pub fn params() -> NoiseParams {
+    CONSTRUCTION.parse().expect("CONSTRUCTION is a valid protocol name")
+}
+
+/// Encode `time` as the 12-byte TAI64N timestamp WireGuard sends as the initiator's payload.
+///
+/// Responders reject a timestamp that isn't greater than the last one seen from the same
+/// peer, so it must be taken from a clock that doesn't go backwards between handshakes.
+#[cfg(feature = "std")]
+#[must_use]
+pub fn tai64n(time: SystemTime) -> [u8; 12] {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut out = [0u8; 12];
+    out[..8].copy_from_slice(&(0x4000_0000_0000_000a + since_epoch.as_secs()).to_be_bytes());
+    out[8..].copy_from_slice(&since_epoch.subsec_nanos().to_be_bytes());
+    out
+}
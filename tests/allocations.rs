@@ -0,0 +1,92 @@
+#![cfg(all(feature = "std", any(feature = "default-resolver", feature = "ring-accelerated")))]
+
+//! Checks that messages are written and read without touching the heap. This needs its own
+//! test binary, since it counts every allocation through the global allocator.
+
+use snow::{params::NoiseParams, Builder};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    // Only this thread's allocations are counted, so the test harness can't get in the way.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // A thread being torn down has no counter left, and isn't the one under test.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations_in(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_messages_dont_allocate() {
+    for name in [
+        "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s",
+        "Noise_XX_25519_AESGCM_SHA256",
+        "Noise_IK_25519_ChaChaPoly_SHA512",
+    ] {
+        let params: NoiseParams = name.parse().unwrap();
+        let static_i = Builder::new(params.clone()).generate_keypair().unwrap();
+        let static_r = Builder::new(params.clone()).generate_keypair().unwrap();
+        // Building the handshake allocates, the messages afterwards don't.
+        let mut b_i = Builder::new(params.clone()).local_private_key(&static_i.private).unwrap();
+        let mut b_r = Builder::new(params.clone()).local_private_key(&static_r.private).unwrap();
+        if params.handshake.pattern.need_known_remote_pubkey(true) {
+            b_i = b_i.remote_public_key(&static_r.public).unwrap();
+        }
+        if params.handshake.is_psk() {
+            b_i = b_i.psk(3, &[7u8; 32]).unwrap();
+            b_r = b_r.psk(3, &[7u8; 32]).unwrap();
+        }
+        let mut h_i = b_i.build_initiator().unwrap();
+        let mut h_r = b_r.build_responder().unwrap();
+
+        let mut message = [0u8; 1024];
+        let mut payload = [0u8; 1024];
+        let handshake = allocations_in(|| {
+            let (mut sender, mut receiver) = (&mut h_i, &mut h_r);
+            while !sender.is_handshake_finished() {
+                let len = sender.write_message(b"payload", &mut message).unwrap();
+                receiver.read_message(&message[..len], &mut payload).unwrap();
+                std::mem::swap(&mut sender, &mut receiver);
+            }
+        });
+        assert_eq!(handshake, 0, "{name} handshake");
+
+        let mut t_i = h_i.into_transport_mode().unwrap();
+        let mut t_r = h_r.into_transport_mode().unwrap();
+        let transport = allocations_in(|| {
+            for _ in 0..10 {
+                let len = t_i.write_message(&[0x42; 512], &mut message).unwrap();
+                t_r.read_message(&message[..len], &mut payload).unwrap();
+                message[..512].fill(0x42);
+                let len = t_r.write_message_in_place(&mut message, 512).unwrap();
+                t_i.read_message_in_place(&mut message[..len]).unwrap();
+            }
+            t_i.rekey_outgoing();
+            t_r.rekey_incoming();
+            let len = t_i.write_message(b"after rekey", &mut message).unwrap();
+            t_r.read_message(&message[..len], &mut payload).unwrap();
+        });
+        assert_eq!(transport, 0, "{name} transport");
+    }
+}
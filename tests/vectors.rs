@@ -1,264 +1,28 @@
 #![cfg(feature = "vector-tests")]
-#[macro_use]
-extern crate serde_derive;
 
-use hex;
-
-use rand;
-use serde_json;
-
-use hex::FromHex;
 use rand::RngCore;
-use serde::{
-    de::{self, Deserialize, Deserializer, Unexpected, Visitor},
-    ser::{Serialize, Serializer},
+use snow::{
+    params::*,
+    resolvers::{BoxedCryptoResolver, CryptoResolver, DefaultResolver},
+    types::{Cipher, Dh, Hash, Random},
+    vectors::{SymmetricOperation, Vector, VectorError, VectorMessage, Vectors},
+    Builder, Keypair,
 };
-use snow::{params::*, Builder, HandshakeState, Keypair};
 use std::{
-    fmt,
     fs::{File, OpenOptions},
-    io::Read,
-    marker::PhantomData,
-    ops::Deref,
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
-#[derive(Clone)]
-struct HexBytes<T> {
-    original: String,
-    payload:  T,
-}
-
-impl<T: AsRef<[u8]>> From<T> for HexBytes<T> {
-    fn from(payload: T) -> Self {
-        Self { original: hex::encode(&payload), payload }
-    }
-}
-
-impl<T> Deref for HexBytes<T> {
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        &self.payload
-    }
-}
-
-impl<T> fmt::Debug for HexBytes<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.original)
-    }
-}
-
-struct HexBytesVisitor<T: AsRef<[u8]>>(PhantomData<T>);
-impl<'de, T: AsRef<[u8]> + FromHex> Visitor<'de> for HexBytesVisitor<T> {
-    type Value = HexBytes<T>;
-
-    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(formatter, "a hex string")
-    }
-
-    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
-    where
-        E: de::Error,
-    {
-        let bytes =
-            T::from_hex(s).map_err(|_| de::Error::invalid_value(Unexpected::Str(s), &self))?;
-        Ok(HexBytes { original: s.to_owned(), payload: bytes })
-    }
-}
-
-impl<'de, T: AsRef<[u8]> + FromHex> Deserialize<'de> for HexBytes<T> {
-    fn deserialize<D>(deserializer: D) -> Result<HexBytes<T>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_str(HexBytesVisitor(Default::default()))
-    }
-}
-
-impl<T: AsRef<[u8]>> Serialize for HexBytes<T> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&hex::encode(&self.payload))
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-struct TestMessage {
-    payload:    HexBytes<Vec<u8>>,
-    ciphertext: HexBytes<Vec<u8>>,
-}
-
-impl fmt::Debug for TestMessage {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Message")
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct TestVector {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>,
-
-    protocol_name: String,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    hybrid: Option<String>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    fail: Option<bool>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    fallback: Option<bool>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    fallback_pattern: Option<String>,
-
-    init_prologue: HexBytes<Vec<u8>>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    init_psks: Option<Vec<HexBytes<[u8; 32]>>>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    init_static: Option<HexBytes<Vec<u8>>>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    init_ephemeral: Option<HexBytes<Vec<u8>>>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    init_remote_static: Option<HexBytes<Vec<u8>>>,
-
-    resp_prologue:      HexBytes<Vec<u8>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    resp_psks:          Option<Vec<HexBytes<[u8; 32]>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    resp_static:        Option<HexBytes<Vec<u8>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    resp_ephemeral:     Option<HexBytes<Vec<u8>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    resp_remote_static: Option<HexBytes<Vec<u8>>>,
-
-    messages: Vec<TestMessage>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct TestVectors {
-    vectors: Vec<TestVector>,
-}
-
-fn build_session_pair(vector: &TestVector) -> Result<(HandshakeState, HandshakeState), String> {
-    let params: NoiseParams = vector.protocol_name.parse().unwrap();
-    let mut init_builder = Builder::new(params.clone());
-    let mut resp_builder = Builder::new(params.clone());
-
-    if params.handshake.is_psk() {
-        let mut psk_index = 0;
-        if let (&Some(ref ipsks), &Some(ref rpsks)) = (&vector.init_psks, &vector.resp_psks) {
-            for modifier in params.handshake.modifiers.list {
-                if let HandshakeModifier::Psk(n) = modifier {
-                    init_builder = init_builder.psk(n, &*ipsks[psk_index]).unwrap();
-                    resp_builder = resp_builder.psk(n, &*rpsks[psk_index]).unwrap();
-                    psk_index += 1;
-                }
-            }
-        } else {
-            return Err("missing PSKs for a PSK-mode handshake".into());
-        }
-    }
-
-    if let Some(ref init_s) = vector.init_static {
-        init_builder = init_builder.local_private_key(&*init_s).unwrap();
-    }
-    if let Some(ref resp_s) = vector.resp_static {
-        resp_builder = resp_builder.local_private_key(&*resp_s).unwrap();
-    }
-    if let Some(ref init_remote_static) = vector.init_remote_static {
-        init_builder = init_builder.remote_public_key(&*init_remote_static).unwrap();
-    }
-    if let Some(ref resp_remote_static) = vector.resp_remote_static {
-        resp_builder = resp_builder.remote_public_key(&*resp_remote_static).unwrap();
-    }
-    if let Some(ref init_e) = vector.init_ephemeral {
-        init_builder = init_builder.fixed_ephemeral_key_for_testing_only(&*init_e);
-    }
-    if let Some(ref resp_e) = vector.resp_ephemeral {
-        resp_builder = resp_builder.fixed_ephemeral_key_for_testing_only(&*resp_e);
-    }
-
-    let init = init_builder
-        .prologue(&vector.init_prologue)
-        .unwrap()
-        .build_initiator()
-        .map_err(|e| format!("{:?}", e))?;
-    let resp = resp_builder
-        .prologue(&vector.resp_prologue)
-        .unwrap()
-        .build_responder()
-        .map_err(|e| format!("{:?}", e))?;
-
-    Ok((init, resp))
-}
-
-fn confirm_message_vectors(
-    mut init: HandshakeState,
-    mut resp: HandshakeState,
-    messages_vec: &Vec<TestMessage>,
-    is_oneway: bool,
-) -> Result<(), String> {
-    let (mut sendbuf, mut recvbuf) = ([0u8; 65535], [0u8; 65535]);
-    let mut messages = messages_vec.iter().enumerate();
-    while !init.is_handshake_finished() {
-        let (i, message) = messages.next().unwrap();
-        let (send, recv) = if i % 2 == 0 { (&mut init, &mut resp) } else { (&mut resp, &mut init) };
-
-        let len = send
-            .write_message(&*message.payload, &mut sendbuf)
-            .map_err(|_| format!("write_message failed on message {}", i))?;
-        let recv_len = recv
-            .read_message(&sendbuf[..len], &mut recvbuf)
-            .map_err(|_| format!("read_message failed on message {}", i))?;
-        if &sendbuf[..len] != &(*message.ciphertext)[..] || *message.payload != &recvbuf[..recv_len]
-        {
-            let mut s = String::new();
-            s.push_str(&format!("message {}", i));
-            s.push_str(&format!("plaintext: {}\n", hex::encode(&*message.payload)));
-            s.push_str(&format!("expected:  {}\n", hex::encode(&*message.ciphertext)));
-            s.push_str(&format!("actual:    {}", hex::encode(&sendbuf[..len].to_owned())));
-            return Err(s);
-        }
-    }
-
-    let (mut init, mut resp) =
-        (init.into_transport_mode().unwrap(), resp.into_transport_mode().unwrap());
-    for (i, message) in messages {
-        let (send, recv) =
-            if is_oneway || i % 2 == 0 { (&mut init, &mut resp) } else { (&mut resp, &mut init) };
-
-        let len = send.write_message(&*message.payload, &mut sendbuf).unwrap();
-        let recv_len = recv.read_message(&sendbuf[..len], &mut recvbuf).unwrap();
-        if &sendbuf[..len] != &(*message.ciphertext)[..] || *message.payload != &recvbuf[..recv_len]
-        {
-            let mut s = String::new();
-            s.push_str(&format!("message {}", i));
-            s.push_str(&format!("plaintext          : {}\n", hex::encode(&*message.payload)));
-            s.push_str(&format!("expected ciphertext: {}\n", hex::encode(&*message.ciphertext)));
-            s.push_str(&format!(
-                "actual ciphertext  : {}\n",
-                hex::encode(&sendbuf[..message.ciphertext.len()].to_owned())
-            ));
-            s.push_str(&format!(
-                "actual plaintext   : {}",
-                hex::encode(&recvbuf[..recv_len].to_owned())
-            ));
-            return Err(s);
-        }
-    }
-    Ok(())
+fn test_vectors_from_json(json: &str) {
+    test_vectors_from_json_with_resolver(json, || Box::new(DefaultResolver));
 }
 
-fn test_vectors_from_json(json: &str) {
-    let test_vectors: TestVectors = serde_json::from_str(json).unwrap();
+fn test_vectors_from_json_with_resolver(json: &str, resolver: impl Fn() -> BoxedCryptoResolver) {
+    let test_vectors = Vectors::from_json(json).unwrap();
 
     let mut passes = 0;
     let mut fails = 0;
@@ -267,35 +31,22 @@ fn test_vectors_from_json(json: &str) {
     for vector in test_vectors.vectors {
         let params: NoiseParams = vector.protocol_name.parse().unwrap();
 
-        if params.dh == DHChoice::Curve448 {
+        if params.dh == DHChoice::Curve448 && !cfg!(feature = "curve448") {
             ignored += 1;
             continue;
         }
 
-        let (init, resp) = match build_session_pair(&vector) {
-            Ok((init, resp)) => (init, resp),
-            Err(s) => {
-                fails += 1;
-                println!("FAIL");
-                println!("{}", s);
-                println!("{:?}", vector);
-                continue;
-            },
-        };
-
-        match confirm_message_vectors(
-            init,
-            resp,
-            &vector.messages,
-            params.handshake.pattern.is_oneway(),
-        ) {
-            Ok(_) => {
+        match vector.run_with_resolver(&resolver) {
+            Ok(()) => {
                 passes += 1;
             },
-            Err(s) => {
+            Err(VectorError::Unsupported) => {
+                ignored += 1;
+            },
+            Err(e) => {
                 fails += 1;
                 println!("FAIL");
-                println!("{}", s);
+                println!("{}", e);
                 println!("{:?}", vector);
             },
         }
@@ -328,14 +79,13 @@ fn get_psks_count(params: &NoiseParams) -> usize {
         .modifiers
         .list
         .iter()
-        .filter(|m| if let HandshakeModifier::Psk(_) = m { true } else { false })
+        .filter(|m| matches!(m, HandshakeModifier::Psk(_)))
         .count()
 }
 
-fn generate_vector(params: NoiseParams) -> TestVector {
+fn generate_vector(params: NoiseParams) -> Vector {
     let prologue = b"There is no right and wrong. There's only fun and boring.".to_vec();
     let mut psks = vec![];
-    let mut psks_hex = vec![];
 
     let (is, ie, rs, re): (Keypair, Keypair, Keypair, Keypair);
 
@@ -348,9 +98,7 @@ fn generate_vector(params: NoiseParams) -> TestVector {
     re = resp_b.generate_keypair().unwrap();
 
     for _ in 0..get_psks_count(&params) {
-        let v = random_slice::<32>();
-        psks_hex.push(v.into());
-        psks.push(v);
+        psks.push(random_slice::<32>());
     }
 
     let mut psk_index = 0;
@@ -387,10 +135,8 @@ fn generate_vector(params: NoiseParams) -> TestVector {
     while !(init.is_handshake_finished() && resp.is_handshake_finished()) {
         let payload = random_vec(32);
         let len = init.write_message(&payload, &mut ibuf).unwrap();
-        messages.push(TestMessage {
-            payload:    payload.clone().into(),
-            ciphertext: ibuf[..len].to_vec().into(),
-        });
+        messages
+            .push(VectorMessage { payload: payload.clone(), ciphertext: ibuf[..len].to_vec() });
         let _ = resp.read_message(&ibuf[..len], &mut obuf).unwrap();
 
         if init.is_handshake_finished() && resp.is_handshake_finished() {
@@ -399,59 +145,53 @@ fn generate_vector(params: NoiseParams) -> TestVector {
 
         let payload = random_vec(32);
         let len = resp.write_message(&payload, &mut ibuf).unwrap();
-        messages.push(TestMessage {
-            payload:    payload.clone().into(),
-            ciphertext: ibuf[..len].to_vec().into(),
-        });
+        messages
+            .push(VectorMessage { payload: payload.clone(), ciphertext: ibuf[..len].to_vec() });
         let _ = init.read_message(&ibuf[..len], &mut obuf).unwrap();
     }
 
     let init_static = if params.handshake.pattern.needs_local_static_key(true) {
-        Some(is.private.to_vec().into())
+        Some(is.private.to_vec())
     } else {
         None
     };
 
     let resp_static = if params.handshake.pattern.needs_local_static_key(false) {
-        Some(rs.private.to_vec().into())
+        Some(rs.private.to_vec())
     } else {
         None
     };
 
     let init_remote_static = if params.handshake.pattern.need_known_remote_pubkey(true) {
-        Some(rs.public.to_vec().into())
+        Some(rs.public.to_vec())
     } else {
         None
     };
 
     let resp_remote_static = if params.handshake.pattern.need_known_remote_pubkey(false) {
-        Some(is.public.to_vec().into())
+        Some(is.public.to_vec())
     } else {
         None
     };
 
-    TestVector {
-        name: None,
+    Vector {
         protocol_name: params.name,
-        hybrid: None,
-        fail: None,
-        fallback: None,
-        fallback_pattern: None,
-        init_prologue: prologue.clone().into(),
-        init_psks: Some(psks_hex.clone()),
+        init_prologue: prologue.clone(),
+        init_psks: psks.iter().map(|psk| psk.to_vec()).collect(),
         init_static,
-        init_ephemeral: Some(ie.private.to_vec().into()),
+        init_ephemeral: Some(ie.private.to_vec()),
         init_remote_static,
-        resp_prologue: prologue.clone().into(),
-        resp_psks: Some(psks_hex.clone()),
+        resp_prologue: prologue,
+        resp_psks: psks.iter().map(|psk| psk.to_vec()).collect(),
         resp_static,
-        resp_ephemeral: Some(re.private.to_vec().into()),
+        resp_ephemeral: Some(re.private.to_vec()),
         resp_remote_static,
         messages,
+        ..Default::default()
     }
 }
 
-fn generate_vector_set() -> TestVectors {
+fn generate_vector_set() -> Vectors {
     let mut handshakes =
         SUPPORTED_HANDSHAKE_PATTERNS.iter().map(|p| p.as_str()).collect::<Vec<&'static str>>();
     handshakes.extend_from_slice(&[
@@ -483,7 +223,7 @@ fn generate_vector_set() -> TestVectors {
             }
         }
     }
-    TestVectors { vectors }
+    Vectors { vectors }
 }
 
 // ignore until noise-c updates the test vectors to new format.
@@ -501,10 +241,106 @@ fn test_vectors_cacophony() {
 fn test_vectors_snow() {
     let file = OpenOptions::new().write(true).create_new(true).open("tests/vectors/snow.txt");
     if let Ok(mut file) = file {
-        serde_json::to_writer_pretty(&mut file, &generate_vector_set()).unwrap();
+        file.write_all(generate_vector_set().to_json().unwrap().as_bytes()).unwrap();
     }
     let mut file = File::open("tests/vectors/snow.txt").unwrap();
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
     test_vectors_from_json(&contents);
 }
+
+/// A resolver that isn't the `DefaultResolver`, standing in for a downstream backend.
+struct CountingResolver {
+    inner:    DefaultResolver,
+    resolved: Arc<AtomicUsize>,
+}
+
+impl CryptoResolver for CountingResolver {
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        self.inner.resolve_rng()
+    }
+
+    fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn Dh>> {
+        self.resolved.fetch_add(1, Ordering::Relaxed);
+        self.inner.resolve_dh(choice)
+    }
+
+    fn resolve_hash(&self, choice: &HashChoice) -> Option<Box<dyn Hash>> {
+        self.resolved.fetch_add(1, Ordering::Relaxed);
+        self.inner.resolve_hash(choice)
+    }
+
+    fn resolve_cipher(&self, choice: &CipherChoice) -> Option<Box<dyn Cipher>> {
+        self.resolved.fetch_add(1, Ordering::Relaxed);
+        self.inner.resolve_cipher(choice)
+    }
+}
+
+#[test]
+fn test_vectors_cacophony_custom_resolver() {
+    let resolved = Arc::new(AtomicUsize::new(0));
+    test_vectors_from_json_with_resolver(include_str!("vectors/cacophony.txt"), || {
+        Box::new(CountingResolver { inner: DefaultResolver, resolved: resolved.clone() })
+    });
+    assert!(resolved.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+#[cfg(all(feature = "hfs", feature = "pqclean_kyber1024"))]
+fn test_vectors_hfs() {
+    // Kyber encapsulation is randomized, so replaying only checks that the payloads survive.
+    let vector = generate_vector("Noise_XXhfs_25519+Kyber1024_ChaChaPoly_BLAKE2s".parse().unwrap());
+    vector.run().unwrap();
+}
+
+#[test]
+fn test_vectors_noise_pipes() {
+    test_vectors_from_json(include_str!("vectors/noise-pipes.txt"));
+}
+
+#[test]
+fn test_vectors_record() {
+    let generated = generate_vector("Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s".parse().unwrap());
+    let handshake_len = generated.messages.len();
+    let mut vector = generated.clone();
+    for message in &mut vector.messages {
+        message.ciphertext.clear();
+    }
+    for payload in [&b"ping"[..], b"pong", b""] {
+        vector.messages.push(VectorMessage { payload: payload.to_vec(), ciphertext: vec![] });
+    }
+
+    let transcript = vector.record().unwrap();
+    let recorded = &transcript.vector;
+    for (recorded, generated) in recorded.messages.iter().zip(&generated.messages) {
+        assert_eq!(recorded.ciphertext, generated.ciphertext);
+    }
+    assert_eq!(recorded.messages.len(), handshake_len + 3);
+
+    // The recorded vector replays, also after a round trip through JSON.
+    let json = Vectors { vectors: vec![recorded.clone()] }.to_json().unwrap();
+    let replayed = Vectors::from_json(&json).unwrap();
+    replayed.vectors[0].run().unwrap();
+    assert!(transcript.to_json().unwrap().contains("\"MixKeyAndHash\""));
+
+    let steps = &transcript.steps;
+    assert_eq!(steps[0].operation, SymmetricOperation::Initialize);
+    assert_eq!(steps[0].h, steps[0].ck);
+    assert_eq!(Some(&steps.last().unwrap().h[..]), recorded.handshake_hash.as_deref());
+    // `ee`, `es` and `se`, plus both ephemeral keys since the pattern has a PSK.
+    assert_eq!(steps.iter().filter(|s| s.operation == SymmetricOperation::MixKey).count(), 5);
+
+    // Message 3 is the first transport message, sent by the responder since its index is odd,
+    // and message 4 the initiator's. Both use nonce 0 under their sender's key.
+    let mut cipher = DefaultResolver.resolve_cipher(&CipherChoice::ChaChaPoly).unwrap();
+    let mut out = [0u8; 64];
+    cipher.set(transcript.responder_send_key[..].try_into().unwrap());
+    let len = cipher.encrypt(0, &[], b"ping", &mut out);
+    assert_eq!(out[..len], recorded.messages[handshake_len].ciphertext[..]);
+    cipher.set(transcript.initiator_send_key[..].try_into().unwrap());
+    let len = cipher.encrypt(0, &[], b"pong", &mut out);
+    assert_eq!(out[..len], recorded.messages[handshake_len + 1].ciphertext[..]);
+
+    vector.fail = true;
+    assert!(matches!(vector.record(), Err(VectorError::Unsupported)));
+}